@@ -45,6 +45,7 @@ lazy_static! {
         settings.set_default("DUMP_REBORROWING_DAG_IN_DEBUG_INFO", false).unwrap();
         settings.set_default("DUMP_BORROWCK_INFO", false).unwrap();
         settings.set_default("DUMP_VIPER_PROGRAM", false).unwrap();
+        settings.set_default("DUMP_TRUST_AUDIT", false).unwrap();
         settings.set_default("FOLDUNFOLD_STATE_FILTER", "").unwrap();
         settings.set_default("CONTRACTS_LIB", "").unwrap();
         settings.set_default::<Vec<String>>("EXTRA_JVM_ARGS", vec![]).unwrap();
@@ -169,6 +170,11 @@ pub fn dump_viper_program() -> bool {
     read_setting("DUMP_VIPER_PROGRAM")
 }
 
+/// Should we dump the audit list of the trusted items of the crate?
+pub fn dump_trust_audit() -> bool {
+    read_setting("DUMP_TRUST_AUDIT")
+}
+
 /// The Viper backend that should be used for the verification
 pub fn foldunfold_state_filter() -> String {
     read_setting("FOLDUNFOLD_STATE_FILTER")
@@ -0,0 +1,67 @@
+// © 2020, ETH Zurich
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! This module defines the audit list of the items that are assumed to be
+//! correct, i.e. those marked with `#[trusted]`.
+
+use report::log;
+use std::io::Write;
+
+/// An item marked with `#[trusted]`, together with the justification given
+/// in `#[trusted(reason = "...", ticket = "...")]`. Items are ordered by
+/// their source location.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TrustedItem {
+    /// The file that contains the item, e.g. `src/ffi.rs`.
+    pub file: String,
+    /// The line of the item, starting at 1.
+    pub line: usize,
+    /// The column of the item, starting at 1.
+    pub column: usize,
+    /// The path of the item, e.g. `ffi::read_bytes`.
+    pub name: String,
+    /// Why the item is assumed to be correct (`prusti::trusted_reason`).
+    pub reason: Option<String>,
+    /// The issue tracking the review of the item (`prusti::trusted_ticket`).
+    pub ticket: Option<String>,
+}
+
+/// Quote a CSV field if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Format the audit list as CSV, sorted by location. Missing reasons and
+/// tickets are left empty.
+pub fn format_trusted_items(items: &[TrustedItem]) -> String {
+    let mut items = items.to_vec();
+    items.sort();
+    let mut csv = String::from("location,item,reason,ticket\n");
+    for item in items {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            csv_field(&format!("{}:{}:{}", item.file, item.line, item.column)),
+            csv_field(&item.name),
+            csv_field(item.reason.as_deref().unwrap_or("")),
+            csv_field(item.ticket.as_deref().unwrap_or("")),
+        ));
+    }
+    csv
+}
+
+/// Write the audit list of the trusted items of the crate `crate_name` to
+/// `trust_audit/<crate_name>.csv` in the log directory.
+pub fn report_trusted_items(crate_name: &str, items: &[TrustedItem]) {
+    log::report_with_writer(
+        "trust_audit",
+        format!("{}.csv", crate_name),
+        |writer| writer.write_all(format_trusted_items(items).as_bytes()).unwrap(),
+    );
+}
@@ -0,0 +1,66 @@
+extern crate prusti_common;
+
+use prusti_common::report::trust_audit::{format_trusted_items, TrustedItem};
+
+fn item(file: &str, line: usize, column: usize, name: &str) -> TrustedItem {
+    TrustedItem {
+        file: file.to_string(),
+        line,
+        column,
+        name: name.to_string(),
+        reason: None,
+        ticket: None,
+    }
+}
+
+#[test]
+fn items_are_sorted_by_file_line_and_column() {
+    let items = vec![
+        item("src/main.rs", 10, 1, "h"),
+        item("src/main.rs", 9, 5, "g"),
+        item("src/ffi.rs", 12, 5, "b"),
+        item("src/main.rs", 9, 12, "f"),
+        item("src/ffi.rs", 100, 1, "c"),
+    ];
+    assert_eq!(
+        format_trusted_items(&items),
+        "location,item,reason,ticket\n\
+         src/ffi.rs:12:5,b,,\n\
+         src/ffi.rs:100:1,c,,\n\
+         src/main.rs:9:5,g,,\n\
+         src/main.rs:9:12,f,,\n\
+         src/main.rs:10:1,h,,\n",
+    );
+}
+
+#[test]
+fn reasons_and_tickets_are_listed() {
+    let items = vec![TrustedItem {
+        reason: Some("wraps libc".to_string()),
+        ticket: Some("#42".to_string()),
+        ..item("src/ffi.rs", 3, 1, "ffi::read")
+    }];
+    assert_eq!(
+        format_trusted_items(&items),
+        "location,item,reason,ticket\nsrc/ffi.rs:3:1,ffi::read,wraps libc,#42\n",
+    );
+}
+
+#[test]
+fn fields_are_quoted_if_needed() {
+    let items = vec![TrustedItem {
+        reason: Some("reads \"raw\" bytes, unchecked".to_string()),
+        ticket: Some("line\nbreak".to_string()),
+        ..item("src/a,b.rs", 1, 1, "<Vec<u8> as Read>::read")
+    }];
+    assert_eq!(
+        format_trusted_items(&items),
+        "location,item,reason,ticket\n\
+         \"src/a,b.rs:1:1\",<Vec<u8> as Read>::read,\"reads \"\"raw\"\" bytes, unchecked\",\"line\nbreak\"\n",
+    );
+}
+
+#[test]
+fn no_items() {
+    assert_eq!(format_trusted_items(&[]), "location,item,reason,ticket\n");
+}
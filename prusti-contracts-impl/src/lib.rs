@@ -2,15 +2,37 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
+use prusti_specs::runtime_checks::{self, insert_runtime_checks, UnboundedQuantifierPolicy};
+use prusti_specs::SpecAttributeKind;
+
+fn unbounded_quantifier_policy() -> UnboundedQuantifierPolicy {
+    if cfg!(feature = "skip-unbounded-quantifiers") {
+        UnboundedQuantifierPolicy::Skip
+    } else {
+        UnboundedQuantifierPolicy::Warn
+    }
+}
 
 #[proc_macro_attribute]
-pub fn requires(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
-    tokens
+pub fn requires(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    if cfg!(feature = "runtime-checks") {
+        insert_runtime_checks(
+            SpecAttributeKind::Requires, attr.into(), tokens.into(), unbounded_quantifier_policy()
+        ).into()
+    } else {
+        tokens
+    }
 }
 
 #[proc_macro_attribute]
-pub fn ensures(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
-    tokens
+pub fn ensures(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    if cfg!(feature = "runtime-checks") {
+        insert_runtime_checks(
+            SpecAttributeKind::Ensures, attr.into(), tokens.into(), unbounded_quantifier_policy()
+        ).into()
+    } else {
+        tokens
+    }
 }
 
 #[proc_macro_attribute]
@@ -33,11 +55,35 @@ pub fn trusted(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
     tokens
 }
 
+#[proc_macro_attribute]
+pub fn invariant(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    tokens
+}
+
+#[proc_macro_attribute]
+pub fn predicate(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    runtime_checks::predicate(attr.into(), tokens.into(), unbounded_quantifier_policy()).into()
+}
+
+#[proc_macro_attribute]
+pub fn decreases(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    tokens
+}
+
 #[proc_macro]
-pub fn body_invariant(_tokens: TokenStream) -> TokenStream {
+pub fn body_variant(_tokens: TokenStream) -> TokenStream {
     (quote! { () }).into()
 }
 
+#[proc_macro]
+pub fn body_invariant(tokens: TokenStream) -> TokenStream {
+    if cfg!(feature = "runtime-checks") {
+        runtime_checks::body_invariant(tokens.into(), unbounded_quantifier_policy()).into()
+    } else {
+        (quote! { () }).into()
+    }
+}
+
 #[proc_macro]
 pub fn closure(tokens: TokenStream) -> TokenStream {
     prusti_specs::closure(tokens.into(), true).into()
@@ -0,0 +1,24 @@
+//! Predicates are executable outside of Prusti, with or without the
+//! `runtime-checks` feature.
+
+use prusti_contracts_impl::predicate;
+
+#[predicate]
+fn in_range(x: u32, n: u32) -> bool {
+    x < n && exists(|i: u32| i <= x && i == x)
+}
+
+#[predicate(forall(|i: usize| i < v.len() ==> v[i] > 0))]
+fn all_positive(v: &[u32]) -> bool;
+
+#[test]
+fn predicate_with_body() {
+    assert!(in_range(3, 10));
+    assert!(!in_range(10, 10));
+}
+
+#[test]
+fn predicate_with_argument() {
+    assert!(all_positive(&[1, 2, 3]));
+    assert!(!all_positive(&[1, 0, 3]));
+}
@@ -0,0 +1,193 @@
+//! Tests of the checks that are inserted with the `runtime-checks` feature,
+//! run with `cargo test -p prusti-contracts-impl --features runtime-checks`.
+
+use prusti_contracts_impl::{ensures, requires};
+use std::num::ParseIntError;
+
+#[requires(x > 0)]
+fn positive(x: u32) -> u32 {
+    x
+}
+
+#[ensures(result == v.len())]
+fn consume(v: Vec<u32>) -> usize {
+    v.into_iter().count()
+}
+
+#[ensures(*y == old(*y) + 1)]
+#[ensures(*result == old(*x))]
+fn bump_and_pick<'a>(x: &'a mut u32, y: &mut u32) -> &'a mut u32 {
+    *y += 1;
+    x
+}
+
+#[ensures(*x == old(*x) + 1)]
+fn increment(x: &mut u32) {
+    *x += 1;
+}
+
+#[ensures(*x == old(*x) + 1)]
+fn increment_wrongly(x: &mut u32) {
+    *x += 2;
+}
+
+#[ensures(result <= 10)]
+fn clamp(x: u32) -> u32 {
+    if x > 10 {
+        return 10;
+    }
+    x
+}
+
+#[ensures(result <= 10)]
+fn clamp_wrongly(x: u32) -> u32 {
+    if x > 20 {
+        return x;
+    }
+    x.min(10)
+}
+
+#[ensures(result.is_ok())]
+fn parse(text: &str) -> Result<u32, ParseIntError> {
+    let value = text.parse()?;
+    Ok(value)
+}
+
+#[ensures(result == x + 1)]
+fn mutate_argument(mut x: u32) -> u32 {
+    x += 1;
+    x
+}
+
+struct NonClone(u32);
+
+#[ensures(result == x.0)]
+fn field_of_non_clone(x: NonClone) -> u32 {
+    x.0
+}
+
+#[ensures(result == x.0 + 1)]
+fn field_of_non_clone_wrongly(x: NonClone) -> u32 {
+    x.0
+}
+
+#[ensures(result != x)]
+fn generic<T: PartialEq>(x: T) -> T {
+    x
+}
+
+#[requires(forall(|i: usize| i < v.len() ==> v[i] > 0))]
+fn all_positive(v: &[u32]) -> usize {
+    v.len()
+}
+
+// each unbounded quantifier is reported by a deprecated item, which must not
+// clash with the others
+#[allow(deprecated)]
+#[requires(forall(|x: i32| x == x))]
+#[requires(forall(|y: i32| y == y))]
+fn unbounded(n: i32) -> i32 {
+    n
+}
+
+#[requires(forall(|x: i32| x > i32::MAX && x <= i32::MAX ==> x < 0))]
+fn empty_range_at_maximum(n: i32) -> i32 {
+    n
+}
+
+#[test]
+fn satisfied_precondition() {
+    assert_eq!(positive(1), 1);
+}
+
+#[test]
+#[should_panic(expected = "precondition violated: `x > 0`")]
+fn violated_precondition() {
+    positive(0);
+}
+
+#[test]
+fn consumed_argument() {
+    assert_eq!(consume(vec![1, 2, 3]), 3);
+}
+
+#[test]
+fn mutable_reference_result() {
+    let mut x = 1;
+    let mut y = 2;
+    *bump_and_pick(&mut x, &mut y) += 1;
+    assert_eq!((x, y), (2, 3));
+}
+
+#[test]
+fn old_value() {
+    let mut x = 1;
+    increment(&mut x);
+    assert_eq!(x, 2);
+}
+
+#[test]
+#[should_panic(expected = "postcondition violated")]
+fn violated_postcondition_with_old_value() {
+    increment_wrongly(&mut 1);
+}
+
+#[test]
+fn early_return() {
+    assert_eq!(clamp(5), 5);
+    assert_eq!(clamp(50), 10);
+}
+
+#[test]
+#[should_panic(expected = "postcondition violated")]
+fn violated_postcondition_on_early_return() {
+    clamp_wrongly(50);
+}
+
+#[test]
+#[should_panic(expected = "postcondition violated")]
+fn violated_postcondition_on_question_mark() {
+    let _ = parse("x");
+}
+
+#[test]
+fn mutated_argument_refers_to_entry_value() {
+    assert_eq!(mutate_argument(1), 2);
+}
+
+#[test]
+fn quantifier_over_unsigned_variable() {
+    assert_eq!(all_positive(&[1, 2]), 2);
+}
+
+#[test]
+#[should_panic(expected = "precondition violated")]
+fn violated_quantifier_over_unsigned_variable() {
+    all_positive(&[1, 0]);
+}
+
+#[test]
+fn unbounded_quantifiers_are_not_checked() {
+    assert_eq!(unbounded(1), 1);
+}
+
+#[test]
+fn strict_lower_bound_at_maximum() {
+    assert_eq!(empty_range_at_maximum(1), 1);
+}
+
+#[test]
+fn copy_field_of_non_clone_parameter() {
+    assert_eq!(field_of_non_clone(NonClone(1)), 1);
+}
+
+#[test]
+#[should_panic(expected = "postcondition violated")]
+fn violated_postcondition_on_copy_field_of_non_clone_parameter() {
+    field_of_non_clone_wrongly(NonClone(1));
+}
+
+#[test]
+fn generic_parameter_without_clone_is_not_checked() {
+    assert_eq!(generic(1), 1);
+}
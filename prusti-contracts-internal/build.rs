@@ -0,0 +1,7 @@
+fn main() {
+    // The macros serialize specifications in the encoding selected by this
+    // variable (see `Encoding::configured` in prusti-specs), so the macro
+    // crate, and with it every crate using the macros, has to be rebuilt
+    // when it changes.
+    println!("cargo:rerun-if-env-changed=PRUSTI_SPEC_ENCODING");
+}
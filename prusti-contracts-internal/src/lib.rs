@@ -5,50 +5,84 @@ use prusti_specs::{rewrite_prusti_attributes, SpecAttributeKind};
 
 #[proc_macro_attribute]
 pub fn requires(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     rewrite_prusti_attributes(SpecAttributeKind::Requires, attr.into(), tokens.into()).into()
 }
 
 #[proc_macro_attribute]
 pub fn ensures(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     rewrite_prusti_attributes(SpecAttributeKind::Ensures, attr.into(), tokens.into()).into()
 }
 
 #[proc_macro_attribute]
 pub fn after_expiry(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     rewrite_prusti_attributes(SpecAttributeKind::AfterExpiry, attr.into(), tokens.into()).into()
 }
 
 #[proc_macro_attribute]
 pub fn after_expiry_if(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     rewrite_prusti_attributes(SpecAttributeKind::AfterExpiryIf, attr.into(), tokens.into()).into()
 }
 
 #[proc_macro_attribute]
 pub fn pure(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     rewrite_prusti_attributes(SpecAttributeKind::Pure, attr.into(), tokens.into()).into()
 }
 
 #[proc_macro_attribute]
 pub fn trusted(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     rewrite_prusti_attributes(SpecAttributeKind::Trusted, attr.into(), tokens.into()).into()
 }
 
+#[proc_macro_attribute]
+pub fn invariant(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
+    rewrite_prusti_attributes(SpecAttributeKind::Invariant, attr.into(), tokens.into()).into()
+}
+
+#[proc_macro_attribute]
+pub fn predicate(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
+    rewrite_prusti_attributes(SpecAttributeKind::Predicate, attr.into(), tokens.into()).into()
+}
+
+#[proc_macro_attribute]
+pub fn decreases(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
+    rewrite_prusti_attributes(SpecAttributeKind::Decreases, attr.into(), tokens.into()).into()
+}
+
+#[proc_macro]
+pub fn body_variant(tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
+    prusti_specs::body_variant(tokens.into()).into()
+}
+
 #[proc_macro]
 pub fn body_invariant(tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     prusti_specs::body_invariant(tokens.into()).into()
 }
 
 #[proc_macro]
 pub fn closure(tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     prusti_specs::closure(tokens.into(), false).into()
 }
 
 #[proc_macro_attribute]
 pub fn refine_trait_spec(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     prusti_specs::refine_trait_spec(attr.into(), tokens.into()).into()
 }
 
 #[proc_macro_attribute]
 pub fn extern_spec(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    prusti_specs::source_info::enable();
     prusti_specs::extern_spec(attr.into(), tokens.into()).into()
 }
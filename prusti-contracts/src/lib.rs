@@ -14,15 +14,27 @@ mod private {
     /// A macro for writing a conditional pledge on a function.
     pub use prusti_contracts_impl::after_expiry_if;
 
-    /// A macro for marking a function as pure.
+    /// A macro for marking a function, or all functions in an item, as pure.
     pub use prusti_contracts_impl::pure;
 
-    /// A macro for marking a function as trusted.
+    /// A macro for marking a function, or all functions in an item, as trusted.
     pub use prusti_contracts_impl::trusted;
 
+    /// A macro for writing a type invariant on a struct or enum.
+    pub use prusti_contracts_impl::invariant;
+
+    /// A macro for defining a named predicate that can be used in specifications.
+    pub use prusti_contracts_impl::predicate;
+
+    /// A macro for writing a termination measure on a function.
+    pub use prusti_contracts_impl::decreases;
+
     /// A macro for writing a loop body invariant.
     pub use prusti_contracts_impl::body_invariant;
 
+    /// A macro for writing a loop body variant.
+    pub use prusti_contracts_impl::body_variant;
+
     /// A macro for defining a closure with a specification.
     pub use prusti_contracts_impl::closure;
 
@@ -47,15 +59,27 @@ mod private {
     /// A macro for writing a conditional pledge on a function.
     pub use prusti_contracts_internal::after_expiry_if;
 
-    /// A macro for marking a function as pure.
+    /// A macro for marking a function, or all functions in an item, as pure.
     pub use prusti_contracts_internal::pure;
 
-    /// A macro for marking a function as trusted.
+    /// A macro for marking a function, or all functions in an item, as trusted.
     pub use prusti_contracts_internal::trusted;
 
+    /// A macro for writing a type invariant on a struct or enum.
+    pub use prusti_contracts_internal::invariant;
+
+    /// A macro for defining a named predicate that can be used in specifications.
+    pub use prusti_contracts_internal::predicate;
+
+    /// A macro for writing a termination measure on a function.
+    pub use prusti_contracts_internal::decreases;
+
     /// A macro for writing a loop body invariant.
     pub use prusti_contracts_internal::body_invariant;
 
+    /// A macro for writing a loop body variant.
+    pub use prusti_contracts_internal::body_variant;
+
     /// A macro for defining a closure with a specification.
     pub use prusti_contracts_internal::closure;
 
@@ -69,12 +93,14 @@ mod private {
 
 /// This function is used to evaluate an expression in the context just
 /// before the borrows expires.
+/// It can only be used in the body of a pledge.
 pub fn before_expiry<T>(arg: T) -> T {
     arg
 }
 
 /// This function is used to evaluate an expression in the “old”
 /// context, that is at the beginning of the method call.
+/// It can only be used in postconditions, pledges and loop invariants.
 pub fn old<T>(arg: T) -> T {
     arg
 }
@@ -83,7 +109,7 @@ pub fn current<T>(arg: T) -> T {
     arg
 }
 
-pub fn final<T>(arg: T) -> T {
+pub fn r#final<T>(arg: T) -> T {
     arg
 }
 
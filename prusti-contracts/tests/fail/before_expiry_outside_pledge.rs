@@ -0,0 +1,11 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[ensures(before_expiry(*result) > 0)]
+fn test(x: &mut u32) -> &mut u32 { x }
+
+fn main() {}
@@ -0,0 +1,27 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+macro_rules! positive {
+    () => {
+        #[requires(x > 0)]
+        pub fn f(x: u32) -> u32 {
+            x
+        }
+    };
+}
+
+mod a {
+    use super::*;
+    positive!();
+}
+
+mod b {
+    use super::*;
+    positive!();
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[requires(f(x ==> true))]
+fn test(x: bool) {}
+
+fn f(b: bool) -> bool { b }
+
+fn main() {}
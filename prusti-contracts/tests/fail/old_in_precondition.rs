@@ -0,0 +1,11 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[requires(old(x) > 0)]
+fn test(x: u32) {}
+
+fn main() {}
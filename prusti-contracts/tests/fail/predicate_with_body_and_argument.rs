@@ -0,0 +1,13 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[predicate(x > 0)]
+fn positive(x: u32) -> bool {
+    x > 0
+}
+
+fn main() {}
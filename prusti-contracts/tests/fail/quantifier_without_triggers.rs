@@ -0,0 +1,11 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[requires(forall(|i: usize| i > 0, patterns = [(i,)]))]
+fn test() {}
+
+fn main() {}
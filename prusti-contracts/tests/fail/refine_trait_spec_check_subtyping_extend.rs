@@ -0,0 +1,25 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+trait Store {
+    #[requires(x > 0)]
+    fn put(&mut self, x: u32);
+}
+
+struct Log {
+    entries: Vec<u32>,
+}
+
+#[refine_trait_spec(check_subtyping, mode = "extend")]
+impl Store for Log {
+    #[requires(x == 0)]
+    fn put(&mut self, x: u32) {
+        self.entries.push(x);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,44 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+trait Buffer {
+    type Item;
+    const CAPACITY: usize;
+    const DEFAULT_LIMIT: usize = 8;
+
+    fn len(&self) -> usize;
+
+    fn push(&mut self, item: Self::Item) -> Self::Item;
+}
+
+struct Ring {
+    items: Vec<u8>,
+}
+
+#[refine_trait_spec]
+impl Buffer for Ring {
+    type Item = u8;
+    const CAPACITY: usize = 16;
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    // `DEFAULT_LIMIT` is not defined here, so it is found through the trait
+    #[requires(self.len() < Self::CAPACITY && self.len() < Self::DEFAULT_LIMIT)]
+    #[ensures(result == old(item))]
+    fn push(&mut self, item: Self::Item) -> Self::Item {
+        self.items.push(item);
+        item
+    }
+}
+
+fn main() {
+    let mut ring = Ring { items: vec![] };
+    assert_eq!(ring.push(1), 1);
+    assert_eq!(ring.len(), 1);
+}
@@ -0,0 +1,24 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[predicate]
+fn in_range(x: u32, n: u32) -> bool {
+    x < n && exists(|i: u32| i <= x && i == x)
+}
+
+#[predicate(forall(|i: usize| i < v.len() ==> v[i] > 0))]
+fn all_positive(v: &[u32]) -> bool;
+
+#[requires(all_positive(v) && in_range(x, 10))]
+fn test(v: &[u32], x: u32) -> u32 {
+    v[0] + x
+}
+
+fn main() {
+    assert!(all_positive(&[1, 2]));
+    assert!(!in_range(10, 10));
+}
@@ -0,0 +1,53 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+trait Store {
+    #[requires(x > 0)]
+    fn put(&mut self, x: u32);
+
+    fn put_all(&mut self, xs: impl IntoIterator<Item = u32>);
+
+    fn put_pair<T: Into<u32>>(&mut self, pair: (T, T));
+
+    fn make<T: Default>() -> T;
+}
+
+struct Log {
+    entries: Vec<u32>,
+}
+
+#[refine_trait_spec(check_subtyping)]
+impl Store for Log {
+    #[requires(true)]
+    fn put(&mut self, x: u32) {
+        self.entries.push(x);
+    }
+
+    #[ensures(self.entries.len() >= old(self.entries.len()))]
+    fn put_all(&mut self, xs: impl IntoIterator<Item = u32>) {
+        self.entries.extend(xs);
+    }
+
+    #[ensures(self.entries.len() == old(self.entries.len()) + 2)]
+    fn put_pair<T: Into<u32>>(&mut self, (a, b): (T, T)) {
+        self.entries.push(a.into());
+        self.entries.push(b.into());
+    }
+
+    #[requires(true)]
+    fn make<T: Default>() -> T {
+        T::default()
+    }
+}
+
+fn main() {
+    let mut log = Log { entries: vec![] };
+    log.put(1);
+    log.put_all(vec![2, 3]);
+    log.put_pair((4u8, 5u8));
+    assert_eq!(log.entries.len(), 5 + Log::make::<usize>());
+}
@@ -0,0 +1,36 @@
+// These feature flags are not needed when executing under Prusti
+// because it generates them for us.
+#![feature(register_tool)]
+#![register_tool(prusti)]
+
+use prusti_contracts::*;
+
+#[ensures(result.len() == 1)]
+#[ensures(<T: Copy + PartialEq> ==> result[0] == x)]
+fn singleton<T>(x: T) -> Vec<T> {
+    vec![x]
+}
+
+#[requires(forall(|i: usize| i < v.len() ==> (<T: PartialOrd> ==> v[i] <= v[i])))]
+fn quantified<T>(v: &[T]) -> usize {
+    v.len()
+}
+
+#[invariant(<T: Copy> ==> self.len > 0)]
+struct Stack<T> {
+    items: Vec<T>,
+    len: usize,
+}
+
+impl<T> Stack<T> {
+    #[ensures(<T: Copy + PartialEq> ==> result == self.items[0])]
+    fn first(&self) -> T where T: Clone {
+        self.items[0].clone()
+    }
+}
+
+struct NotCopy;
+
+fn main() {
+    singleton(NotCopy);
+}
@@ -2,5 +2,10 @@
 fn tests() {
     let runner = trybuild::TestCases::new();
     runner.pass("tests/pass/*.rs");
-    runner.compile_fail("tests/fail/*.rs");
+    // Without Prusti the specifications are not parsed, so the malformed
+    // ones only fail to compile with the `prusti` feature enabled, as in
+    // `cargo test -p prusti-contracts --features prusti`.
+    if cfg!(feature = "prusti") {
+        runner.compile_fail("tests/fail/*.rs");
+    }
 }
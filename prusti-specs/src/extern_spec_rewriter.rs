@@ -12,13 +12,16 @@ use syn::spanned::Spanned;
 /// they are specifying.
 pub fn rewrite_mod(item_mod: &mut syn::ItemMod, path: &mut syn::Path) -> syn::Result<()> {
     if item_mod.content.is_none() {
-        return Ok(())
+        return Err(syn::Error::new(
+            item_mod.span(),
+            "external specifications of a module must be written inline, in a `mod { ... }` block",
+        ))
     }
 
-    path.segments.push(syn::PathSegment { ident: item_mod.ident.clone(), arguments: syn::PathArguments::None });
     let name_generator = NameGenerator::new();
-    item_mod.ident = syn::Ident::new(&name_generator.generate_mod_name(&item_mod.ident),
-                                    item_mod.span());
+    let mod_name = name_generator.generate_mod_name(path, &item_mod.ident);
+    path.segments.push(syn::PathSegment { ident: item_mod.ident.clone(), arguments: syn::PathArguments::None });
+    item_mod.ident = syn::Ident::new(&mod_name, item_mod.span());
 
     for item in item_mod.content.as_mut().unwrap().1.iter_mut() {
         match item {
@@ -42,37 +45,52 @@ pub fn rewrite_mod(item_mod: &mut syn::ItemMod, path: &mut syn::Path) -> syn::Re
                     }
                     new_tokens.extend(token.to_token_stream());
                 }
-                let res: Result<syn::Item, _> = syn::parse2(new_tokens);
-                if res.is_err() {
-                    return Err(syn::Error::new(
+                let mut new_item = match syn::parse2(new_tokens) {
+                    Ok(syn::Item::Fn(item_fn)) => item_fn,
+                    _ => return Err(syn::Error::new(
                         item.span(),
                         "invalid function signature",
-                    ))
-                }
-
-                let mut item = res.unwrap();
-                if let syn::Item::Fn(item_fn) = &mut item {
-                    rewrite_fn(item_fn, path);
-                }
+                    )),
+                };
+                rewrite_fn(&mut new_item, path);
                 *tokens = quote! {
-                    #item
+                    #new_item
                 }
             }
             syn::Item::Use(_) => {}
             _ => return Err(syn::Error::new(
                 item.span(),
-                "unexpected item",
+                format!("{} are not supported in external specifications of a module",
+                        describe_item(item)),
             ))
         }
     }
     Ok(())
 }
 
+/// Describe the kind of an item for error messages.
+fn describe_item(item: &syn::Item) -> &'static str {
+    match item {
+        syn::Item::Const(_) => "constants",
+        syn::Item::Static(_) => "statics",
+        syn::Item::Struct(_) => "structs",
+        syn::Item::Enum(_) => "enums",
+        syn::Item::Union(_) => "unions",
+        syn::Item::Type(_) => "type aliases",
+        syn::Item::Trait(_) | syn::Item::TraitAlias(_) => "traits",
+        syn::Item::Impl(_) => "impl blocks",
+        syn::Item::Macro(_) | syn::Item::Macro2(_) => "macro items",
+        syn::Item::ExternCrate(_) => "extern crate declarations",
+        syn::Item::ForeignMod(_) => "extern blocks",
+        _ => "these items",
+    }
+}
+
 /// Rewrite a specification function to a call to the specified function.
 /// The result of this rewriting is then parsed in `ExternSpecResolver`.
 fn rewrite_fn(item_fn: &mut syn::ItemFn, path: &mut syn::Path) {
+    let args = rewrite_inputs(&mut item_fn.sig.inputs);
     let ident = &item_fn.sig.ident;
-    let args = &item_fn.sig.inputs;
     item_fn.block = syn::parse_quote! {
         {
             #path :: #ident (#args);
@@ -85,11 +103,15 @@ fn rewrite_fn(item_fn: &mut syn::ItemFn, path: &mut syn::Path) {
 }
 
 /// Rewrite all methods in an impl block to calls to the specified methods.
+/// Methods of a trait impl are called with a fully-qualified path
+/// `<Type as Trait>::method`, and the impl is turned into an inherent impl.
+/// In both cases, `Self` is replaced by the specified type.
 /// The result of this rewriting is then parsed in `ExternSpecResolver`.
 pub fn rewrite_impl(
     impl_item: &mut syn::ItemImpl,
     new_ty: Box<syn::Type>,
 ) -> syn::Result<TokenStream> {
+    let trait_path = impl_item.trait_.take().map(|(_, path, _)| path);
     let item_ty = &mut impl_item.self_ty;
     if let syn::Type::Path(type_path) = item_ty.as_mut() {
         for seg in type_path.path.segments.iter_mut() {
@@ -98,13 +120,21 @@ pub fn rewrite_impl(
             }
         }
     }
+    let self_ty = item_ty.to_token_stream();
+    let callee = match &trait_path {
+        Some(trait_path) => quote! { <#item_ty as #trait_path> },
+        None => quote! { #item_ty },
+    };
 
     for item in impl_item.items.iter_mut() {
         match item {
             syn::ImplItem::Method(method) => {
                 for attr in method.attrs.iter_mut() {
-                    attr.tokens = rewrite_self(attr.tokens.clone());
+                    attr.tokens = rewrite_self(rewrite_self_type(attr.tokens.clone(), &self_ty));
                 }
+                let sig = rewrite_self_type(method.sig.to_token_stream(), &self_ty);
+                let rewritten: ImplItemMethod = syn::parse2(quote! { #sig {} })?;
+                method.sig = rewritten.sig;
 
                 let args = rewrite_method_inputs(item_ty, method);
                 let ident = &method.sig.ident;
@@ -114,11 +144,23 @@ pub fn rewrite_impl(
 
                 method.block = syn::parse_quote! {
                     {
-                        #item_ty :: #ident (#args);
+                        #callee :: #ident (#args);
                         unimplemented!()
                     }
                 };
             }
+            syn::ImplItem::Const(_) => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "associated constants are not supported in external specifications",
+                ));
+            }
+            syn::ImplItem::Type(_) => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "associated types are not supported in external specifications",
+                ));
+            }
             _ => {
                 return Err(syn::Error::new(
                     item.span(),
@@ -134,6 +176,116 @@ pub fn rewrite_impl(
     })
 }
 
+/// Rewrite all methods of a trait to calls to the specified trait methods,
+/// put into an impl block of the struct generated by
+/// `generate_new_struct_for_trait`. `Self` is replaced by its type parameter
+/// `_Self`, which is bounded by the trait, so that the specifications apply
+/// to all implementations of the trait.
+/// The result of this rewriting is then parsed in `ExternSpecResolver`.
+pub fn rewrite_trait(
+    item_trait: &syn::ItemTrait,
+    new_struct: &syn::ItemStruct,
+) -> syn::Result<TokenStream> {
+    let trait_ident = &item_trait.ident;
+    let (_, trait_ty_generics, _) = item_trait.generics.split_for_impl();
+    let self_ty: Box<syn::Type> = box syn::parse_quote! { _Self };
+    let self_ty_tokens = self_ty.to_token_stream();
+
+    let mut methods: Vec<ImplItemMethod> = vec![];
+    for item in item_trait.items.iter() {
+        match item {
+            syn::TraitItem::Method(trait_method) => {
+                let attrs = trait_method.attrs.iter().map(|attr| {
+                    let mut attr = attr.clone();
+                    attr.tokens = rewrite_self(rewrite_self_type(attr.tokens, &self_ty_tokens));
+                    attr
+                });
+                let sig = rewrite_self_type(trait_method.sig.to_token_stream(), &self_ty_tokens);
+                let mut method: ImplItemMethod = syn::parse2(quote! {
+                    #(#attrs)*
+                    #sig {}
+                })?;
+
+                let args = rewrite_method_inputs(&self_ty, &mut method);
+                let ident = &method.sig.ident;
+
+                method.attrs.push(syn::parse_quote! { #[prusti::extern_spec] });
+                method.attrs.push(syn::parse_quote! { #[trusted] });
+
+                method.block = syn::parse_quote! {
+                    {
+                        <_Self as #trait_ident #trait_ty_generics> :: #ident (#args);
+                        unimplemented!()
+                    }
+                };
+                methods.push(method);
+            }
+            syn::TraitItem::Const(_) => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "associated constants are not supported in external specifications",
+                ));
+            }
+            syn::TraitItem::Type(_) => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "associated types are not supported in external specifications",
+                ));
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "expected a method".to_string(),
+                ));
+            }
+        }
+    }
+
+    let struct_ident = &new_struct.ident;
+    let (impl_generics, struct_ty_generics, _) = new_struct.generics.split_for_impl();
+    let mut where_clause: syn::WhereClause = match &item_trait.generics.where_clause {
+        Some(where_clause) => syn::parse2(
+            rewrite_self_type(where_clause.to_token_stream(), &self_ty_tokens)
+        )?,
+        None => syn::parse_quote! { where },
+    };
+    where_clause.predicates.push(syn::parse_quote! { _Self: #trait_ident #trait_ty_generics });
+
+    Ok(quote! {
+        impl #impl_generics #struct_ident #struct_ty_generics #where_clause {
+            #(#methods)*
+        }
+    })
+}
+
+/// Replace the `Self` type by the given type.
+fn rewrite_self_type(
+    tokens: proc_macro2::TokenStream,
+    self_ty: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut new_tokens = proc_macro2::TokenStream::new();
+    for token in tokens.into_iter() {
+        match token {
+            proc_macro2::TokenTree::Group(group) => {
+                let mut new_group = proc_macro2::Group::new(group.delimiter(),
+                                                            rewrite_self_type(group.stream(), self_ty));
+                new_group.set_span(group.span());
+                new_tokens.extend(new_group.to_token_stream());
+            }
+            proc_macro2::TokenTree::Ident(ident) if ident == "Self" => {
+                // a group keeps a path type such as `Vec<T>` together in `Self::Item`
+                let mut group = proc_macro2::Group::new(proc_macro2::Delimiter::None, self_ty.clone());
+                group.set_span(ident.span());
+                new_tokens.extend(group.to_token_stream());
+            }
+            _ => {
+                new_tokens.extend(token.into_token_stream());
+            }
+        }
+    }
+    new_tokens
+}
+
 fn rewrite_self(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
     let mut new_tokens = proc_macro2::TokenStream::new();
     for token in tokens.into_iter() {
@@ -158,33 +310,50 @@ fn rewrite_self(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
 }
 
 fn rewrite_method_inputs(item_ty: &Box<syn::Type>, method: &mut ImplItemMethod) ->
+    syn::punctuated::Punctuated<syn::Expr, syn::token::Comma> {
+    for input in method.sig.inputs.iter_mut() {
+        if let syn::FnArg::Receiver(receiver) = input {
+            let and = if receiver.reference.is_some() {
+                // TODO: do lifetimes need to be specified here?
+                quote! {&}
+            } else {
+                quote! { }
+            };
+            let mutability = &receiver.mutability;
+            let fn_arg: syn::FnArg = syn::parse_quote! { _self : #and #mutability #item_ty };
+            *input = fn_arg;
+        }
+    }
+    rewrite_inputs(&mut method.sig.inputs)
+}
+
+/// Collect the arguments with which the specified function is called.
+/// Arguments bound by a pattern other than an identifier, such as `_` or
+/// `(a, b)`, are renamed to `_prusti_arg_<index>`, so that they can be
+/// passed on; the bindings of such patterns cannot be used in specifications.
+fn rewrite_inputs(inputs: &mut syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>) ->
     syn::punctuated::Punctuated<syn::Expr, syn::token::Comma> {
     let mut args: syn::punctuated::Punctuated<syn::Expr, syn::token::Comma> =
         syn::punctuated::Punctuated::new();
 
-    for input in method.sig.inputs.iter_mut() {
-        match input {
+    for (index, input) in inputs.iter_mut().enumerate() {
+        let arg = match input {
             syn::FnArg::Receiver(receiver) => {
-                let and = if receiver.reference.is_some() {
-                    // TODO: do lifetimes need to be specified here?
-                    quote! {&}
-                } else {
-                    quote! { }
-                };
-                let mutability = &receiver.mutability;
-                let fn_arg: syn::FnArg = syn::parse_quote! { _self : #and #mutability #item_ty };
-                *input = fn_arg;
-                let expr: syn::Expr = syn::parse_quote! { _self };
-                args.push_value(expr);
-            }
-            syn::FnArg::Typed(typed) => {
-                if let syn::Pat::Ident(ident) = &*typed.pat {
-                    let arg = &ident.ident;
-                    let expr: syn::Expr = syn::parse_quote! { #arg };
-                    args.push_value(expr);
+                syn::Ident::new("self", receiver.self_token.span)
+            }
+            syn::FnArg::Typed(typed) => match &*typed.pat {
+                syn::Pat::Ident(syn::PatIdent { ident, by_ref: None, subpat: None, .. }) => {
+                    ident.clone()
+                }
+                pat => {
+                    let ident = syn::Ident::new(&format!("_prusti_arg_{}", index), pat.span());
+                    typed.pat = box syn::parse_quote! { #ident };
+                    ident
                 }
             }
-        }
+        };
+        let expr: syn::Expr = syn::parse_quote! { #arg };
+        args.push_value(expr);
         args.push_punct(syn::token::Comma::default());
     };
     args
@@ -197,32 +366,60 @@ pub fn generate_new_struct(item: &syn::ItemImpl) -> syn::Result<syn::ItemStruct>
     let struct_name = match name_generator.generate_struct_name(item) {
         Ok(name) => name,
         Err(msg) => return Err(syn::Error::new(
-            item.span(),
+            item.self_ty.span(),
             msg,
         ))
     };
     let struct_ident = syn::Ident::new(&struct_name,
                                        item.span());
 
-    let mut new_struct: syn::ItemStruct = syn::parse_quote! {
-        struct #struct_ident {}
+    let mut generics = item.generics.clone();
+    generics.where_clause = None;
+    let fields = phantom_data_fields(&generics);
+    let new_struct: syn::ItemStruct = syn::parse_quote! {
+        struct #struct_ident #generics (#(#fields),*);
     };
+    Ok(new_struct)
+}
 
-    new_struct.generics = item.generics.clone();
-    let generics = &new_struct.generics;
+/// Generate an empty struct to be able to define an impl block (in
+/// `rewrite_trait`) on it for the specification functions of a trait. The
+/// struct is generic over the implementing type `_Self` and the type
+/// parameters of the trait.
+pub fn generate_new_struct_for_trait(item: &syn::ItemTrait) -> syn::ItemStruct {
+    let name_generator = NameGenerator::new();
+    let struct_ident = syn::Ident::new(
+        &name_generator.generate_trait_struct_name(item),
+        item.span(),
+    );
 
-    let mut fields_str: String = String::new();
+    let mut generics = item.generics.clone();
+    generics.where_clause = None;
+    let self_param: syn::GenericParam = syn::parse_quote! { _Self };
+    let position = generics.lifetimes().count();
+    generics.params.insert(position, self_param);
 
-    // Add `PhantomData` markers for each type parameter to silence errors
-    // about unused type parameters.
-    for param in generics.params.iter() {
-        let field = format!("std::marker::PhantomData<{}>,", param.to_token_stream().to_string());
-        fields_str.push_str(&field);
-    }
+    let fields = phantom_data_fields(&generics);
 
-    let fields : syn::FieldsUnnamed =
-        syn::parse_str(&format!("({})", fields_str)).unwrap();
+    syn::parse_quote! {
+        struct #struct_ident #generics (#(#fields),*);
+    }
+}
 
-    new_struct.fields = syn::Fields::Unnamed(fields);
-    Ok(new_struct)
+/// Generate `PhantomData` markers for each parameter to silence errors about
+/// unused parameters.
+fn phantom_data_fields(generics: &syn::Generics) -> Vec<syn::Type> {
+    generics.params.iter().filter_map(|param| -> Option<syn::Type> {
+        match param {
+            syn::GenericParam::Type(param) => {
+                let ident = &param.ident;
+                Some(syn::parse_quote! { std::marker::PhantomData<#ident> })
+            }
+            syn::GenericParam::Lifetime(param) => {
+                let lifetime = &param.lifetime;
+                Some(syn::parse_quote! { std::marker::PhantomData<&#lifetime ()> })
+            }
+            syn::GenericParam::Const(_) => None,
+        }
+    }).collect()
 }
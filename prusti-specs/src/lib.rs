@@ -1,20 +1,26 @@
 #![feature(box_syntax)]
 #![feature(box_patterns)]
 #![feature(drain_filter)]
+#![feature(proc_macro_span)]
+
+extern crate proc_macro;
 
 mod extern_spec_rewriter;
 mod rewriter;
 mod parse_closure_macro;
 mod spec_attribute_kind;
 pub mod specifications;
+pub mod runtime_checks;
+pub mod source_info;
 
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use syn::spanned::Spanned;
 use syn::parse_quote;
 use std::convert::{TryFrom, TryInto};
 
 use specifications::untyped;
+use specifications::common::RefinementMode;
 use parse_closure_macro::ClosureWithSpec;
 pub use spec_attribute_kind::SpecAttributeKind;
 
@@ -27,8 +33,8 @@ macro_rules! handle_result {
     };
 }
 
-fn extract_prusti_attributes<'a>(item: &'a mut untyped::AnyFnItem) -> impl Iterator<Item=(SpecAttributeKind, TokenStream)> + 'a {
-    item.attrs_mut().drain_filter(
+fn extract_prusti_attributes<'a>(attrs: &'a mut Vec<syn::Attribute>) -> impl Iterator<Item=(SpecAttributeKind, TokenStream)> + 'a {
+    attrs.drain_filter(
         |attr|
             attr.path.segments.len() == 1
                 && SpecAttributeKind::try_from(attr.path.segments[0].ident.to_string()).is_ok()
@@ -48,6 +54,20 @@ pub fn rewrite_prusti_attributes(
     outer_attr_tokens: TokenStream,
     item_tokens: TokenStream,
 ) -> TokenStream {
+    if let SpecAttributeKind::Invariant = outer_attr_kind {
+        return rewrite_type_invariants(outer_attr_tokens, item_tokens);
+    }
+    if let SpecAttributeKind::Predicate = outer_attr_kind {
+        return predicate(outer_attr_tokens, item_tokens);
+    }
+    if let SpecAttributeKind::Pure | SpecAttributeKind::Trusted = outer_attr_kind {
+        if let Ok(item) = syn::parse2::<syn::Item>(item_tokens.clone()) {
+            if let syn::Item::Impl(_) | syn::Item::Trait(_) | syn::Item::Mod(_) = item {
+                return rewrite_bulk_attribute(outer_attr_kind, outer_attr_tokens, item);
+            }
+        }
+    }
+
     let mut item: untyped::AnyFnItem = handle_result!(syn::parse2(item_tokens));
 
     // Start with the outer attribute
@@ -56,7 +76,7 @@ pub fn rewrite_prusti_attributes(
     ];
 
     // Collect the remaining Prusti attributes, removing them from `item`.
-    prusti_attributes.extend(extract_prusti_attributes(&mut item));
+    prusti_attributes.extend(extract_prusti_attributes(item.attrs_mut()));
 
     let (generated_spec_items, generated_attributes) = handle_result!(
         generate_spec_and_assertions(prusti_attributes, &item)
@@ -69,6 +89,193 @@ pub fn rewrite_prusti_attributes(
     }
 }
 
+/// Rewrite an impl block, a trait or an inline module annotated with `#[trusted]` or `#[pure]`
+/// by attaching the attribute to every function in it. Functions and nested items with their
+/// own attribute of the same kind, such as `#[trusted(false)]`, are left to that attribute.
+/// Only functions that return a value are marked as pure.
+fn rewrite_bulk_attribute(
+    attr_kind: SpecAttributeKind,
+    attr_tokens: TokenStream,
+    mut item: syn::Item,
+) -> TokenStream {
+    let (_, attributes) = match attr_kind {
+        SpecAttributeKind::Pure => handle_result!(generate_for_pure(attr_tokens, None)),
+        SpecAttributeKind::Trusted => handle_result!(generate_for_trusted(attr_tokens, None)),
+        _ => unreachable!(),
+    };
+    if attributes.is_empty() {
+        return quote! { #item };
+    }
+    let name = match attr_kind {
+        SpecAttributeKind::Pure => "pure",
+        _ => "trusted",
+    };
+    let propagator = BulkAttributePropagator { attr_kind, name, attributes };
+    handle_result!(propagator.propagate_to_item(&mut item));
+    quote! { #item }
+}
+
+/// Attaches the attribute of a `#[trusted]` or `#[pure]` impl block, trait or module to the
+/// functions in it.
+struct BulkAttributePropagator {
+    attr_kind: SpecAttributeKind,
+    /// The name of the Prusti attribute, `trusted` or `pure`.
+    name: &'static str,
+    /// The attributes to attach to the functions.
+    attributes: Vec<syn::Attribute>,
+}
+
+impl BulkAttributePropagator {
+    /// Whether one of `attrs` is the propagated attribute, also when written
+    /// with a path such as `prusti_contracts::trusted`.
+    fn has_own_attribute(&self, attrs: &[syn::Attribute]) -> bool {
+        attrs.iter().any(|attr| {
+            attr.path.segments.last().map_or(false, |segment| segment.ident == self.name)
+        })
+    }
+
+    fn propagate_to_fn(&self, attrs: &mut Vec<syn::Attribute>, sig: &syn::Signature) {
+        if self.has_own_attribute(attrs) {
+            return;
+        }
+        if let (SpecAttributeKind::Pure, syn::ReturnType::Default) = (self.attr_kind, &sig.output) {
+            return;
+        }
+        attrs.extend(self.attributes.iter().cloned());
+    }
+
+    fn propagate_to_item(&self, item: &mut syn::Item) -> syn::Result<()> {
+        match item {
+            syn::Item::Fn(item_fn) => self.propagate_to_fn(&mut item_fn.attrs, &item_fn.sig),
+            syn::Item::Impl(item_impl) => {
+                for impl_item in &mut item_impl.items {
+                    if let syn::ImplItem::Method(method) = impl_item {
+                        self.propagate_to_fn(&mut method.attrs, &method.sig);
+                    }
+                }
+            }
+            syn::Item::Trait(item_trait) => {
+                for trait_item in &mut item_trait.items {
+                    if let syn::TraitItem::Method(method) = trait_item {
+                        self.propagate_to_fn(&mut method.attrs, &method.sig);
+                    }
+                }
+            }
+            syn::Item::Mod(item_mod) => {
+                let items = match &mut item_mod.content {
+                    Some((_, items)) => items,
+                    None => return Err(syn::Error::new(
+                        item_mod.span(),
+                        format!("`#[{}]` can only be applied to inline modules", self.name),
+                    )),
+                };
+                for item in items {
+                    let has_own_attribute = match item {
+                        syn::Item::Impl(syn::ItemImpl { attrs, .. })
+                        | syn::Item::Trait(syn::ItemTrait { attrs, .. })
+                        | syn::Item::Mod(syn::ItemMod { attrs, .. }) => self.has_own_attribute(attrs),
+                        _ => false,
+                    };
+                    if !has_own_attribute {
+                        self.propagate_to_item(item)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Rewrite a struct or an enum as required by *all* its `invariant` attributes.
+fn rewrite_type_invariants(
+    outer_attr_tokens: TokenStream,
+    item_tokens: TokenStream,
+) -> TokenStream {
+    let mut item: untyped::AnyTypeItem = handle_result!(syn::parse2(item_tokens));
+
+    // Start with the outer attribute
+    let mut prusti_attributes = vec![
+        (SpecAttributeKind::Invariant, outer_attr_tokens)
+    ];
+
+    // Collect the remaining Prusti attributes, removing them from `item`.
+    prusti_attributes.extend(extract_prusti_attributes(item.attrs_mut()));
+
+    let mut generated_spec_items = vec![];
+    let mut generated_attributes = vec![];
+    for (index, (attr_kind, attr_tokens)) in prusti_attributes.into_iter().enumerate() {
+        let mut rewriter = rewriter::AstRewriter::new(
+            handle_result!(spec_id_seed(item.ident().span(), attr_kind, index, &attr_tokens, &item.ident()))
+        );
+        let (new_items, new_attributes) = match attr_kind {
+            SpecAttributeKind::Invariant => handle_result!(
+                generate_for_invariant(&mut rewriter, attr_tokens, &item)
+            ),
+            _ => {
+                return syn::Error::new(
+                    item.ident().span(),
+                    "only `invariant` specifications can be attached to structs and enums",
+                ).to_compile_error();
+            }
+        };
+        generated_spec_items.extend(new_items);
+        generated_attributes.extend(new_attributes);
+    }
+
+    quote!{
+        #(#generated_spec_items)*
+        #(#generated_attributes)*
+        #item
+    }
+}
+
+/// Rewrite a `#[predicate]` function into a spec item that type-checks its
+/// assertion and a pure, trusted function that can be called from other
+/// specifications.
+///
+/// The compiler parses the body of the function as Rust, which rejects
+/// operators such as `==>`. Such assertions are given as the argument of the
+/// attribute on a function without a body instead, as in
+/// `#[predicate(forall(|i: usize| i < v.len() ==> v[i] > 0))] fn all_positive(v: &[u32]) -> bool;`.
+pub fn predicate(attr: TokenStream, item_tokens: TokenStream) -> TokenStream {
+    let untyped::PredicateFnItem { mut item, assertion } = handle_result!(
+        untyped::PredicateFnItem::parse(attr, item_tokens)
+    );
+    if extract_prusti_attributes(&mut item.attrs).next().is_some() {
+        return syn::Error::new(
+            item.sig.ident.span(),
+            "predicates cannot have other specifications",
+        ).to_compile_error();
+    }
+    // the body is not verified, but evaluates the assertion if it is executed
+    item.block = handle_result!(runtime_checks::predicate_body(
+        assertion.clone(), runtime_checks::UnboundedQuantifierPolicy::Skip
+    ));
+    let item = untyped::AnyFnItem::Fn(item);
+
+    let mut rewriter = rewriter::AstRewriter::new(
+        handle_result!(spec_id_seed(item.sig().ident.span(), SpecAttributeKind::Predicate, 0, &assertion, item.sig()))
+    );
+    let spec_id = rewriter.generate_spec_id();
+    let spec_id_str = spec_id.to_string();
+    let assertion = handle_result!(rewriter.parse_assertion(spec_id, assertion));
+    let spec_items = handle_result!(rewriter.generate_spec_item_fn(
+        rewriter::SpecItemType::Predicate,
+        spec_id,
+        assertion,
+        &item
+    ));
+    quote! {
+        #(#spec_items)*
+        #[allow(unused_variables, dead_code)]
+        #[prusti::pure]
+        #[prusti::trusted]
+        #[prusti::pred_spec_id_ref = #spec_id_str]
+        #item
+    }
+}
+
 type GeneratedResult = syn::Result<(Vec<syn::Item>, Vec<syn::Attribute>)>;
 
 /// Generate spec items and attributes for `item` from the Prusti attributes
@@ -79,14 +286,27 @@ fn generate_spec_and_assertions(
     let mut generated_items = vec![];
     let mut generated_attributes = vec![];
 
-    for (attr_kind, attr_tokens) in prusti_attributes.drain(..) {
+    for (index, (attr_kind, attr_tokens)) in prusti_attributes.drain(..).enumerate() {
+        let mut rewriter = rewriter::AstRewriter::new(
+            spec_id_seed(item.sig().ident.span(), attr_kind, index, &attr_tokens, item.sig())?
+        );
+        let rewriter = &mut rewriter;
         let rewriting_result = match attr_kind {
-            SpecAttributeKind::Requires => generate_for_requires(attr_tokens, item),
-            SpecAttributeKind::Ensures => generate_for_ensures(attr_tokens, item),
-            SpecAttributeKind::AfterExpiry => generate_for_after_expiry(attr_tokens, item),
-            SpecAttributeKind::AfterExpiryIf => generate_for_after_expiry_if(attr_tokens, item),
-            SpecAttributeKind::Pure => generate_for_pure(attr_tokens, item),
-            SpecAttributeKind::Trusted => generate_for_trusted(attr_tokens, item),
+            SpecAttributeKind::Requires => generate_for_requires(rewriter, attr_tokens, item),
+            SpecAttributeKind::Ensures => generate_for_ensures(rewriter, attr_tokens, item),
+            SpecAttributeKind::AfterExpiry => generate_for_after_expiry(rewriter, attr_tokens, item),
+            SpecAttributeKind::AfterExpiryIf => generate_for_after_expiry_if(rewriter, attr_tokens, item),
+            SpecAttributeKind::Pure => generate_for_pure(attr_tokens, Some(item)),
+            SpecAttributeKind::Trusted => generate_for_trusted(attr_tokens, Some(item)),
+            SpecAttributeKind::Decreases => generate_for_decreases(rewriter, attr_tokens, item),
+            SpecAttributeKind::Invariant => Err(syn::Error::new(
+                item.sig().ident.span(),
+                "type invariants can only be attached to structs and enums",
+            )),
+            SpecAttributeKind::Predicate => Err(syn::Error::new(
+                item.sig().ident.span(),
+                "predicates cannot have other specifications",
+            )),
         };
         let (new_items, new_attributes) = rewriting_result?;
         generated_items.extend(new_items);
@@ -96,83 +316,155 @@ fn generate_spec_and_assertions(
     Ok((generated_items, generated_attributes))
 }
 
+/// Build the seed from which the identifiers generated for a specification
+/// of an item are derived, so that an unchanged specification always gets
+/// the same identifiers: the file of the item, the item, the kind and
+/// position of the attribute, and the tokens of the attribute. Fails at
+/// `span` if the seed is not unique in the crate.
+fn spec_id_seed(
+    span: Span,
+    attr_kind: impl std::fmt::Debug,
+    index: usize,
+    attr_tokens: &TokenStream,
+    item: &dyn ToTokens,
+) -> syn::Result<String> {
+    let seed = format!(
+        "{}|{:?}|{}|{}|{}",
+        source_info::crate_file(span),
+        attr_kind,
+        index,
+        attr_tokens,
+        item.to_token_stream(),
+    );
+    specifications::common::register_seed(&seed, span)?;
+    Ok(seed)
+}
+
+/// Like `spec_id_seed`, for a specification in a function body, which is
+/// told apart from identical ones in the same file by its position among
+/// them.
+fn body_spec_id_seed(span: Span, kind: &str, tokens: &TokenStream) -> String {
+    specifications::common::number_seed(
+        format!("{}|{}|{}", source_info::crate_file(span), kind, tokens)
+    )
+}
+
 /// Generate spec items and attributes to typecheck the and later retrieve "requires" annotations.
-fn generate_for_requires(attr: TokenStream, item: &untyped::AnyFnItem) -> GeneratedResult {
-    let mut rewriter = rewriter::AstRewriter::new();
+fn generate_for_requires(
+    rewriter: &mut rewriter::AstRewriter,
+    attr: TokenStream,
+    item: &untyped::AnyFnItem,
+) -> GeneratedResult {
     let spec_id = rewriter.generate_spec_id();
     let spec_id_str = spec_id.to_string();
     let assertion = rewriter.parse_assertion(spec_id, attr)?;
-    let spec_item = rewriter.generate_spec_item_fn(
+    let spec_items = rewriter.generate_spec_item_fn(
         rewriter::SpecItemType::Precondition,
         spec_id,
         assertion,
         &item
     )?;
     Ok((
-        vec![spec_item],
+        spec_items,
         vec![parse_quote!(#[prusti::pre_spec_id_ref = #spec_id_str])],
     ))
 }
 
 /// Generate spec items and attributes to typecheck th and later retrieve "ensures" annotations.
-fn generate_for_ensures(attr: TokenStream, item: &untyped::AnyFnItem) -> GeneratedResult {
-    let mut rewriter = rewriter::AstRewriter::new();
+fn generate_for_ensures(
+    rewriter: &mut rewriter::AstRewriter,
+    attr: TokenStream,
+    item: &untyped::AnyFnItem,
+) -> GeneratedResult {
     let spec_id = rewriter.generate_spec_id();
     let spec_id_str = spec_id.to_string();
     let assertion = rewriter.parse_assertion(spec_id, attr)?;
-    let spec_item = rewriter.generate_spec_item_fn(
+    let spec_items = rewriter.generate_spec_item_fn(
         rewriter::SpecItemType::Postcondition,
         spec_id,
         assertion,
         &item
     )?;
     Ok((
-        vec![spec_item],
+        spec_items,
         vec![parse_quote!(#[prusti::post_spec_id_ref = #spec_id_str])],
     ))
 }
 
-/// Check if the given expression is identifier `result`.
-fn check_is_result(reference: &Option<untyped::Expression>) -> syn::Result<()> {
+/// Check if the given pledge reference is `result` or a parameter of the
+/// function, optionally followed by field accesses (for example `result.0`).
+fn check_pledge_reference(
+    reference: &Option<untyped::Expression>,
+    item: &untyped::AnyFnItem,
+) -> syn::Result<()> {
     if let Some(untyped::Expression { expr, ..}) = reference {
-        if let syn::Expr::Path(syn::ExprPath { qself: None, path, ..}) = expr {
-            if path.is_ident("result") {
-                return Ok(());
+        check_pledge_reference_expr(expr, item)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_pledge_reference_expr(expr: &syn::Expr, item: &untyped::AnyFnItem) -> syn::Result<()> {
+    match expr {
+        syn::Expr::Field(syn::ExprField { base, .. }) => check_pledge_reference_expr(base, item),
+        syn::Expr::Paren(syn::ExprParen { expr, .. }) => check_pledge_reference_expr(expr, item),
+        syn::Expr::Path(syn::ExprPath { qself: None, path, ..}) if path.get_ident().is_some() => {
+            let ident = path.get_ident().unwrap();
+            let is_param = item.sig().inputs.iter().any(|input| match input {
+                syn::FnArg::Receiver(_) => ident == "self",
+                syn::FnArg::Typed(syn::PatType {
+                    pat: box syn::Pat::Ident(syn::PatIdent { ident: param, .. }),
+                    ..
+                }) => ident == param,
+                _ => false,
+            });
+            if ident == "result" || is_param {
+                Ok(())
+            } else {
+                Err(syn::Error::new(
+                    ident.span(),
+                    format!("`{}` is neither `result` nor a parameter of the function", ident),
+                ))
             }
         }
-        Err(syn::Error::new(
+        _ => Err(syn::Error::new(
             expr.span(),
-            "currently only `result` is supported".to_string(),
-        ))
-    } else {
-        Ok(())
+            "the reference of a pledge must be `result` or a parameter, \
+             optionally followed by field accesses".to_string(),
+        )),
     }
 }
 
 /// Generate spec items and attributes to typecheck and later retrieve "after_expiry" annotations.
-fn generate_for_after_expiry(attr: TokenStream, item: &untyped::AnyFnItem) -> GeneratedResult {
-    let mut rewriter = rewriter::AstRewriter::new();
+fn generate_for_after_expiry(
+    rewriter: &mut rewriter::AstRewriter,
+    attr: TokenStream,
+    item: &untyped::AnyFnItem,
+) -> GeneratedResult {
     let spec_id_rhs = rewriter.generate_spec_id();
     let spec_id_rhs_str = format!(":{}", spec_id_rhs);
     let pledge = rewriter.parse_pledge(None, spec_id_rhs, attr)?;
-    check_is_result(&pledge.reference)?;
+    check_pledge_reference(&pledge.reference, item)?;
     assert!(pledge.lhs.is_none(), "after_expiry with lhs?");
-    let spec_item_rhs = rewriter.generate_spec_item_fn(
-        rewriter::SpecItemType::Postcondition,
+    let spec_items_rhs = rewriter.generate_spec_item_pledge_rhs(
         spec_id_rhs,
         pledge.rhs,
+        pledge.reference.as_ref(),
         &item
     )?;
     Ok((
-        vec![spec_item_rhs],
+        spec_items_rhs,
         vec![parse_quote!(#[prusti::pledge_spec_id_ref = #spec_id_rhs_str])],
     ))
 }
 
 /// Generate spec items and attributes to typecheck and later retrieve "after_expiry_if"
 /// annotations.
-fn generate_for_after_expiry_if(attr: TokenStream, item: &untyped::AnyFnItem) -> GeneratedResult {
-    let mut rewriter = rewriter::AstRewriter::new();
+fn generate_for_after_expiry_if(
+    rewriter: &mut rewriter::AstRewriter,
+    attr: TokenStream,
+    item: &untyped::AnyFnItem,
+) -> GeneratedResult {
     let spec_id_lhs = rewriter.generate_spec_id();
     let spec_id_rhs = rewriter.generate_spec_id();
     let spec_id_str = format!("{}:{}", spec_id_lhs, spec_id_rhs);
@@ -181,46 +473,201 @@ fn generate_for_after_expiry_if(attr: TokenStream, item: &untyped::AnyFnItem) ->
         spec_id_rhs,
         attr
     )?;
-    check_is_result(&pledge.reference)?;
-    let spec_item_lhs = rewriter.generate_spec_item_fn(
+    check_pledge_reference(&pledge.reference, item)?;
+    let mut spec_items = rewriter.generate_spec_item_fn(
         rewriter::SpecItemType::Postcondition,
         spec_id_lhs,
         pledge.lhs.unwrap(),
         &item
     )?;
-    let spec_item_rhs = rewriter.generate_spec_item_fn(
-        rewriter::SpecItemType::Postcondition,
+    spec_items.extend(rewriter.generate_spec_item_pledge_rhs(
         spec_id_rhs,
         pledge.rhs,
+        pledge.reference.as_ref(),
         &item
-    )?;
+    )?);
     Ok((
-        vec![spec_item_lhs, spec_item_rhs],
+        spec_items,
         vec![parse_quote!(#[prusti::pledge_spec_id_ref = #spec_id_str])],
     ))
 }
 
-/// Generate spec items and attributes to typecheck and later retrieve "pure" annotations.
-fn generate_for_pure(_attr: TokenStream, _item: &untyped::AnyFnItem) -> GeneratedResult {
+/// Generate spec items and attributes to typecheck and later retrieve "decreases" annotations.
+fn generate_for_decreases(
+    rewriter: &mut rewriter::AstRewriter,
+    attr: TokenStream,
+    item: &untyped::AnyFnItem,
+) -> GeneratedResult {
+    let spec_id = rewriter.generate_spec_id();
+    let spec_id_str = spec_id.to_string();
+    let variant = rewriter.parse_variant(spec_id, attr)?;
+    let spec_item = rewriter.generate_spec_item_variant(spec_id, variant, item)?;
     Ok((
-        vec![],
-        vec![parse_quote!(#[prusti::pure])],
+        vec![spec_item],
+        vec![parse_quote!(#[prusti::decreases_spec_id_ref = #spec_id_str])],
     ))
 }
 
-/// Generate spec items and attributes to typecheck and later retrieve "trusted" annotations.
-fn generate_for_trusted(_attr: TokenStream, _item: &untyped::AnyFnItem) -> GeneratedResult {
+/// Generate spec items and attributes to typecheck and later retrieve "invariant" annotations.
+fn generate_for_invariant(
+    rewriter: &mut rewriter::AstRewriter,
+    attr: TokenStream,
+    item: &untyped::AnyTypeItem,
+) -> GeneratedResult {
+    let spec_id = rewriter.generate_spec_id();
+    let spec_id_str = spec_id.to_string();
+    let assertion = rewriter.parse_assertion(spec_id, attr)?;
+    rewriter.check_contextual_functions(
+        rewriter::SpecContext::of(rewriter::SpecItemType::TypeInvariant), &assertion, vec![]
+    )?;
+    rewriter::check_type_condition_params(&assertion.type_conditions(), item.generics(), false)?;
+    let spec_item = rewriter.generate_spec_item_type_invariant(spec_id, assertion, item)?;
     Ok((
-        vec![],
-        vec![parse_quote!(#[prusti::trusted])],
+        vec![spec_item],
+        vec![parse_quote!(#[prusti::type_invariant_spec_id_ref = #spec_id_str])],
     ))
 }
 
+/// Generate a spec item and an attribute that record a behavioral subtyping
+/// obligation of a trait implementation method.
+fn generate_for_refinement(
+    spec_type: rewriter::SpecItemType,
+    trait_path: &syn::Path,
+    item: &untyped::AnyFnItem,
+) -> syn::Result<(syn::Item, syn::Attribute)> {
+    let mut rewriter = rewriter::AstRewriter::new(
+        spec_id_seed(item.sig().ident.span(), spec_type, 0, &trait_path.to_token_stream(), item.sig())?
+    );
+    let spec_id = rewriter.generate_spec_id();
+    let spec_id_str = spec_id.to_string();
+    let spec_item = rewriter.generate_spec_item_refinement(spec_type, spec_id, trait_path, item)?;
+    let attribute = match spec_type {
+        rewriter::SpecItemType::PreconditionRefinement => {
+            parse_quote!(#[prusti::pre_refinement_spec_id_ref = #spec_id_str])
+        }
+        _ => parse_quote!(#[prusti::post_refinement_spec_id_ref = #spec_id_str]),
+    };
+    Ok((spec_item, attribute))
+}
+
+/// Parse the argument of `#[trusted]` or `#[pure]`: none or `true` to enable the attribute,
+/// or `false` to opt out of the attribute of an enclosing impl block, trait or module.
+fn parse_enabled_flag(attr: TokenStream) -> syn::Result<bool> {
+    let parser = |input: syn::parse::ParseStream| -> syn::Result<bool> {
+        let content;
+        let input = if input.peek(syn::token::Paren) {
+            syn::parenthesized!(content in input);
+            &content
+        } else {
+            input
+        };
+        if input.is_empty() {
+            return Ok(true);
+        }
+        let flag: syn::LitBool = input.parse().map_err(
+            |err| syn::Error::new(err.span(), "expected `true` or `false`")
+        )?;
+        Ok(flag.value)
+    };
+    syn::parse::Parser::parse2(parser, attr)
+}
+
+/// The arguments of `#[trusted]`: none, `true`, `false` or the justification of the trust, for
+/// example `#[trusted(reason = "wraps a C library", ticket = "SEC-123")]`.
+struct TrustedOptions {
+    enabled: bool,
+    /// Why the item is assumed to be correct.
+    reason: Option<syn::LitStr>,
+    /// The issue tracking the review of the item.
+    ticket: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for TrustedOptions {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut options = TrustedOptions { enabled: true, reason: None, ticket: None };
+        let content;
+        let input = if input.peek(syn::token::Paren) {
+            syn::parenthesized!(content in input);
+            &content
+        } else {
+            input
+        };
+        if input.is_empty() || input.peek(syn::LitBool) {
+            options.enabled = parse_enabled_flag(input.parse()?)?;
+            return Ok(options);
+        }
+        let metas = syn::punctuated::Punctuated::<syn::MetaNameValue, syn::Token![,]>::parse_terminated(input)?;
+        for meta in metas {
+            let field = if meta.path.is_ident("reason") {
+                &mut options.reason
+            } else if meta.path.is_ident("ticket") {
+                &mut options.ticket
+            } else {
+                return Err(syn::Error::new(
+                    meta.path.span(),
+                    "unknown argument of `trusted`, expected `reason` or `ticket`",
+                ));
+            };
+            if field.is_some() {
+                return Err(syn::Error::new(meta.path.span(), "duplicate argument of `trusted`"));
+            }
+            match meta.lit {
+                syn::Lit::Str(value) => *field = Some(value),
+                lit => return Err(syn::Error::new(lit.span(), "expected a string literal")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Generate spec items and attributes to typecheck and later retrieve "pure" annotations.
+fn generate_for_pure(attr: TokenStream, _item: Option<&untyped::AnyFnItem>) -> GeneratedResult {
+    let attributes = if parse_enabled_flag(attr)? {
+        vec![parse_quote!(#[prusti::pure])]
+    } else {
+        vec![]
+    };
+    Ok((vec![], attributes))
+}
+
+/// Generate spec items and attributes to typecheck and later retrieve "trusted" annotations and
+/// their justification.
+fn generate_for_trusted(attr: TokenStream, _item: Option<&untyped::AnyFnItem>) -> GeneratedResult {
+    let options: TrustedOptions = syn::parse2(attr)?;
+    let mut attributes = vec![];
+    if options.enabled {
+        attributes.push(parse_quote!(#[prusti::trusted]));
+        if let Some(reason) = options.reason {
+            attributes.push(parse_quote!(#[prusti::trusted_reason = #reason]));
+        }
+        if let Some(ticket) = options.ticket {
+            attributes.push(parse_quote!(#[prusti::trusted_ticket = #ticket]));
+        }
+    }
+    Ok((vec![], attributes))
+}
+
 pub fn body_invariant(tokens: TokenStream) -> TokenStream {
-    let mut rewriter = rewriter::AstRewriter::new();
+    let mut rewriter = rewriter::AstRewriter::new(
+        body_spec_id_seed(tokens.span(), "body_invariant", &tokens)
+    );
     let spec_id = rewriter.generate_spec_id();
     let invariant = handle_result!(rewriter.parse_assertion(spec_id, tokens));
-    let check = rewriter.generate_spec_loop(spec_id, invariant);
+    let check = handle_result!(rewriter.generate_spec_loop(spec_id, invariant));
+    quote! {
+        if false {
+            #check
+        }
+    }
+}
+
+pub fn body_variant(tokens: TokenStream) -> TokenStream {
+    let mut rewriter = rewriter::AstRewriter::new(
+        body_spec_id_seed(tokens.span(), "body_variant", &tokens)
+    );
+    let spec_id = rewriter.generate_spec_id();
+    let variant = handle_result!(rewriter.parse_variant(spec_id, tokens));
+    let check = handle_result!(rewriter.generate_spec_loop_variant(spec_id, variant));
     quote! {
         if false {
             #check
@@ -236,7 +683,7 @@ pub fn body_invariant(tokens: TokenStream) -> TokenStream {
 /// the function whether to keep the specification (for -internal) or
 /// drop it (for -impl).
 pub fn closure(tokens: TokenStream, drop_spec: bool) -> TokenStream {
-    let cl_spec = syn::parse::<ClosureWithSpec>(tokens.into());
+    let cl_spec = syn::parse2::<ClosureWithSpec>(tokens);
     if let Err(err) = cl_spec {
         return err.to_compile_error();
     }
@@ -245,7 +692,9 @@ pub fn closure(tokens: TokenStream, drop_spec: bool) -> TokenStream {
     if drop_spec {
         cl_spec.cl.into_token_stream()
     } else {
-        let mut rewriter = rewriter::AstRewriter::new();
+        let mut rewriter = rewriter::AstRewriter::new(
+            body_spec_id_seed(cl_spec.cl.span(), "closure", &cl_spec.cl.to_token_stream())
+        );
 
         let mut preconds: Vec<(untyped::SpecificationId, untyped::Assertion)> = Vec::new();
         let mut postconds: Vec<(untyped::SpecificationId, untyped::Assertion)> = Vec::new();
@@ -272,7 +721,7 @@ pub fn closure(tokens: TokenStream, drop_spec: bool) -> TokenStream {
             });
         }
 
-        let (spec_toks_pre, spec_toks_post) = rewriter.generate_cl_spec(preconds, postconds);
+        let (spec_toks_pre, spec_toks_post) = handle_result!(rewriter.generate_cl_spec(preconds, postconds));
         let syn::ExprClosure {
             attrs, asyncness, movability, capture, or1_token,
             inputs, or2_token, output, body
@@ -305,39 +754,160 @@ pub fn closure(tokens: TokenStream, drop_spec: bool) -> TokenStream {
     }
 }
 
-pub fn refine_trait_spec(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
+/// The options of `refine_trait_spec`, for example
+/// `#[refine_trait_spec(check_subtyping)]` or
+/// `#[refine_trait_spec(mode = "extend")]`.
+struct RefineTraitSpecOptions {
+    /// Whether the refined specifications are checked to be behavioral
+    /// subtypes of the trait's specifications. Extended specifications are
+    /// behavioral subtypes by construction, so this cannot be combined with
+    /// `mode = "extend"`.
+    check_subtyping: bool,
+    /// How the refined specifications are combined with the trait's
+    /// specifications.
+    mode: specifications::common::RefinementMode,
+}
+
+impl syn::parse::Parse for RefineTraitSpecOptions {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut options = RefineTraitSpecOptions {
+            check_subtyping: false,
+            mode: Default::default(),
+        };
+        let metas = syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated(input)?;
+        for meta in metas {
+            match meta {
+                syn::Meta::Path(path) if path.is_ident("check_subtyping") => {
+                    options.check_subtyping = true;
+                }
+                syn::Meta::NameValue(syn::MetaNameValue { path, lit, .. }) if path.is_ident("mode") => {
+                    options.mode = match &lit {
+                        syn::Lit::Str(mode) => mode.value().as_str().try_into().map_err(|_| {
+                            syn::Error::new(
+                                lit.span(),
+                                "unknown refinement mode, expected `\"overwrite\"` or `\"extend\"`",
+                            )
+                        })?,
+                        _ => return Err(syn::Error::new(
+                            lit.span(),
+                            "expected a string literal, for example `mode = \"extend\"`",
+                        )),
+                    };
+                }
+                _ => return Err(syn::Error::new(
+                    meta.span(),
+                    "unknown option of `refine_trait_spec`, expected `check_subtyping` or `mode`",
+                )),
+            }
+        }
+        Ok(options)
+    }
+}
+
+pub fn refine_trait_spec(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+    let options: RefineTraitSpecOptions = handle_result!(syn::parse2(attr.clone()));
     let mut impl_block: syn::ItemImpl = handle_result!(syn::parse2(tokens));
+    let trait_path = impl_block.trait_.as_ref().map(|(_, path, _)| path.clone());
+    if options.check_subtyping && trait_path.is_none() {
+        return syn::Error::new(
+            attr.span(),
+            "`check_subtyping` can only be used on implementations of traits",
+        ).to_compile_error();
+    }
+    if options.mode != RefinementMode::Overwrite && trait_path.is_none() {
+        return syn::Error::new(
+            attr.span(),
+            "refinement modes can only be used on implementations of traits",
+        ).to_compile_error();
+    }
+    if options.check_subtyping && options.mode == RefinementMode::Extend {
+        return syn::Error::new(
+            attr.span(),
+            "`check_subtyping` cannot be used with `mode = \"extend\"`, which always refines \
+            the trait's specifications to behavioral subtypes",
+        ).to_compile_error();
+    }
+    // the spec items are put into an inherent impl block, in which the
+    // associated types and constants of the trait are not in scope
+    let associated_items: Vec<syn::Ident> = impl_block.items.iter().filter_map(|item| {
+        match item {
+            syn::ImplItem::Type(item_type) => Some(item_type.ident.clone()),
+            syn::ImplItem::Const(item_const) => Some(item_const.ident.clone()),
+            _ => None,
+        }
+    }).collect();
     let mut new_items = Vec::new();
     let mut generated_spec_items = Vec::new();
     for item in impl_block.items {
         match item {
             syn::ImplItem::Method(method) => {
                 let mut method_item = untyped::AnyFnItem::ImplMethod(method);
-                let prusti_attributes: Vec<_> = extract_prusti_attributes(&mut method_item).collect();
-                let (spec_items, generated_attributes) = handle_result!(
+                let prusti_attributes: Vec<_> = extract_prusti_attributes(method_item.attrs_mut()).collect();
+                // only the refined parts of the specification lead to obligations
+                let mut refinements = vec![];
+                if options.check_subtyping {
+                    if prusti_attributes.iter().any(|(kind, _)| matches!(kind, SpecAttributeKind::Requires)) {
+                        refinements.push(rewriter::SpecItemType::PreconditionRefinement);
+                    }
+                    if prusti_attributes.iter().any(|(kind, _)| matches!(kind, SpecAttributeKind::Ensures)) {
+                        refinements.push(rewriter::SpecItemType::PostconditionRefinement);
+                    }
+                }
+                let has_specs = !prusti_attributes.is_empty();
+                let (mut spec_items, mut generated_attributes) = handle_result!(
                     generate_spec_and_assertions(prusti_attributes, &method_item)
                 );
-                generated_spec_items.extend(spec_items.into_iter().map(|spec_item| {
+                if has_specs && options.mode != RefinementMode::Overwrite {
+                    let mode = options.mode.as_str();
+                    generated_attributes.push(parse_quote! {
+                        #[prusti::refinement_mode = #mode]
+                    });
+                }
+                if let Some(trait_path) = &trait_path {
+                    for spec_type in refinements {
+                        let (spec_item, attribute) = handle_result!(
+                            generate_for_refinement(spec_type, trait_path, &method_item)
+                        );
+                        spec_items.push(spec_item);
+                        generated_attributes.push(attribute);
+                    }
+                }
+                for spec_item in spec_items {
                     match spec_item {
                         syn::Item::Fn(spec_item_fn) => {
-                            syn::ImplItem::Method(syn::ImplItemMethod {
+                            generated_spec_items.push(syn::ImplItem::Method(syn::ImplItemMethod {
                                 attrs: spec_item_fn.attrs,
                                 vis: spec_item_fn.vis,
                                 defaultness: None,
                                 sig: spec_item_fn.sig,
                                 block: *spec_item_fn.block,
-                            })
+                            }));
+                        }
+                        other => {
+                            return syn::Error::new(
+                                other.span(),
+                                "this specification is not supported in impl blocks that refine \
+                                trait specifications",
+                            ).to_compile_error();
                         }
-                        x => unimplemented!("Unexpected variant: {:?}", x),
                     }
-                }));
+                }
                 let new_item = parse_quote!{
                     #(#generated_attributes)*
                     #method_item
                 };
                 new_items.push(new_item);
             }
-            _ => {}
+            other => new_items.push(other),
+        }
+    }
+    if let Some(trait_path) = &trait_path {
+        let mut qualifier = rewriter::AssociatedItemQualifier {
+            trait_path,
+            associated_items: &associated_items,
+        };
+        for spec_item in &mut generated_spec_items {
+            syn::visit_mut::VisitMut::visit_impl_item_mut(&mut qualifier, spec_item);
         }
     }
     impl_block.items = new_items;
@@ -365,10 +935,10 @@ pub fn extern_spec(_attr: TokenStream, tokens:TokenStream) -> TokenStream {
             let new_struct = handle_result!(extern_spec_rewriter::generate_new_struct(&mut item_impl));
 
             let struct_ident = &new_struct.ident;
-            let generics = &new_struct.generics;
+            let (_, ty_generics, _) = new_struct.generics.split_for_impl();
 
             let struct_ty: syn::Type = syn::parse_quote! {
-                #struct_ident #generics
+                #struct_ident #ty_generics
             };
 
             let rewritten_item =
@@ -389,6 +959,21 @@ pub fn extern_spec(_attr: TokenStream, tokens:TokenStream) -> TokenStream {
                 #item_mod
             }
         }
-        _ => { unimplemented!() }
+        syn::Item::Trait(item_trait) => {
+            let new_struct = extern_spec_rewriter::generate_new_struct_for_trait(&item_trait);
+            let rewritten_item =
+                handle_result!(extern_spec_rewriter::rewrite_trait(&item_trait, &new_struct));
+
+            quote! {
+                #new_struct
+                #rewritten_item
+            }
+        }
+        _ => {
+            syn::Error::new(
+                item.span(),
+                "`#[extern_spec]` can only be applied to impl blocks, traits and modules",
+            ).to_compile_error()
+        }
     }
 }
@@ -1,18 +1,27 @@
 use crate::specifications::common::{ExpressionIdGenerator, SpecificationIdGenerator};
 use crate::specifications::untyped::{self, EncodeTypeCheck};
 use proc_macro2::{Span, TokenStream};
-use quote::{quote, format_ident};
+use quote::{quote, quote_spanned, format_ident, ToTokens};
+use std::collections::HashMap;
 use syn::spanned::Spanned;
+use syn::visit::Visit;
 
 pub(crate) struct AstRewriter {
     expr_id_generator: ExpressionIdGenerator,
     spec_id_generator: SpecificationIdGenerator,
+    /// The messages of the parsed clauses, by the IDs of their specifications.
+    messages: HashMap<untyped::SpecificationId, String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SpecItemType {
     Precondition,
     Postcondition,
+    TypeInvariant,
+    Predicate,
+    Variant,
+    PreconditionRefinement,
+    PostconditionRefinement,
 }
 
 impl std::fmt::Display for SpecItemType {
@@ -20,15 +29,54 @@ impl std::fmt::Display for SpecItemType {
         match self {
             SpecItemType::Precondition => write!(f, "pre"),
             SpecItemType::Postcondition => write!(f, "post"),
+            SpecItemType::TypeInvariant => write!(f, "invariant"),
+            SpecItemType::Predicate => write!(f, "pred"),
+            SpecItemType::Variant => write!(f, "decreases"),
+            SpecItemType::PreconditionRefinement => write!(f, "pre_refinement"),
+            SpecItemType::PostconditionRefinement => write!(f, "post_refinement"),
         }
     }
 }
 
+/// The contextual functions and variables that an assertion may use, which
+/// depend on the states in which it is evaluated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SpecContext {
+    /// Whether `old(e)` may refer to the state on entry of the function.
+    old: bool,
+    /// Whether `result` may be used. In loop invariants and closure
+    /// specifications it may also be a local variable, so it is allowed.
+    result: bool,
+    /// Whether `before_expiry(e)` may refer to the state just before the
+    /// borrow expires.
+    before_expiry: bool,
+}
+
+impl SpecContext {
+    /// The body of a pledge, which is evaluated after the borrow expires.
+    pub(crate) const PLEDGE: Self = Self { old: true, result: true, before_expiry: true };
+    /// A loop invariant or variant.
+    pub(crate) const LOOP: Self = Self { old: true, result: true, before_expiry: false };
+    /// A precondition of a closure.
+    pub(crate) const CLOSURE_PRECONDITION: Self = Self { old: false, result: true, before_expiry: false };
+    /// A postcondition of a closure.
+    pub(crate) const CLOSURE_POSTCONDITION: Self = Self { old: true, result: true, before_expiry: false };
+
+    /// The context of the assertions of a spec item.
+    pub(crate) fn of(spec_type: SpecItemType) -> Self {
+        let is_postcondition = spec_type == SpecItemType::Postcondition;
+        Self { old: is_postcondition, result: is_postcondition, before_expiry: false }
+    }
+}
+
 impl AstRewriter {
-    pub(crate) fn new() -> Self {
+    /// Create a rewriter whose specification identifiers are derived from
+    /// `seed`.
+    pub(crate) fn new(seed: String) -> Self {
         Self {
             expr_id_generator: ExpressionIdGenerator::new(),
-            spec_id_generator: SpecificationIdGenerator::new(),
+            spec_id_generator: SpecificationIdGenerator::new(seed),
+            messages: HashMap::new(),
         }
     }
 
@@ -36,13 +84,33 @@ impl AstRewriter {
         self.spec_id_generator.generate()
     }
 
-    /// Parse an assertion.
+    /// Parse an assertion, remembering its message for serialization.
     pub fn parse_assertion(
         &mut self,
         spec_id: untyped::SpecificationId,
         tokens: TokenStream,
     ) -> syn::Result<untyped::Assertion> {
-        untyped::Assertion::parse(tokens, spec_id, &mut self.expr_id_generator)
+        let (assertion, message) = untyped::Assertion::parse(
+            tokens, spec_id, &mut self.expr_id_generator
+        )?;
+        if let Some(message) = message {
+            self.messages.insert(spec_id, message);
+        }
+        Ok(assertion)
+    }
+
+    /// The message of the clause with the given specification ID.
+    fn message(&self, spec_id: untyped::SpecificationId) -> Option<&str> {
+        self.messages.get(&spec_id).map(String::as_str)
+    }
+
+    /// Parse a termination measure.
+    pub fn parse_variant(
+        &mut self,
+        spec_id: untyped::SpecificationId,
+        tokens: TokenStream,
+    ) -> syn::Result<untyped::Variant> {
+        untyped::Variant::parse(tokens, spec_id, &mut self.expr_id_generator)
     }
 
     /// Parse a pledge.
@@ -52,7 +120,14 @@ impl AstRewriter {
         spec_id_rhs: untyped::SpecificationId,
         tokens: TokenStream
     ) -> syn::Result<untyped::Pledge> {
-        untyped::Pledge::parse(tokens, spec_id_lhs, spec_id_rhs, &mut self.expr_id_generator)
+        let (pledge, message) = untyped::Pledge::parse(
+            tokens, spec_id_lhs, spec_id_rhs, &mut self.expr_id_generator
+        )?;
+        if let Some(message) = message {
+            self.messages.extend(spec_id_lhs.map(|spec_id| (spec_id, message.clone())));
+            self.messages.insert(spec_id_rhs, message);
+        }
+        Ok(pledge)
     }
 
     /// Check whether function `item` contains a parameter called `keyword`. If
@@ -89,7 +164,8 @@ impl AstRewriter {
         fn_arg
     }
 
-    /// Generate a dummy function for checking the given precondition or postcondition.
+    /// Generate a dummy function for checking the given precondition or postcondition,
+    /// followed by one for the body of each of its type conditions.
     ///
     /// `spec_type` should be either `"pre"` or `"post"`.
     pub fn generate_spec_item_fn(
@@ -98,21 +174,71 @@ impl AstRewriter {
         spec_id: untyped::SpecificationId,
         assertion: untyped::Assertion,
         item: &untyped::AnyFnItem,
-    ) -> syn::Result<syn::Item> {
+    ) -> syn::Result<Vec<syn::Item>> {
+        self.generate_spec_item_fn_with_checks(
+            spec_type, SpecContext::of(spec_type), spec_id, assertion, TokenStream::new(), item
+        )
+    }
+
+    /// Generate a dummy function for checking the rhs of a pledge. The
+    /// reference of the pledge, if given, is type-checked in the same function.
+    pub fn generate_spec_item_pledge_rhs(
+        &mut self,
+        spec_id: untyped::SpecificationId,
+        rhs: untyped::Assertion,
+        reference: Option<&untyped::Expression>,
+        item: &untyped::AnyFnItem,
+    ) -> syn::Result<Vec<syn::Item>> {
+        let mut checks = TokenStream::new();
+        if let Some(reference) = reference {
+            let span = reference.expr.span();
+            let expr = &reference.expr;
+            let identifier = format!("{}_{}", reference.spec_id, reference.id);
+            checks.extend(quote_spanned! { span =>
+                #[prusti::spec_only]
+                #[prusti::pledge_reference]
+                #[prusti::expr_id = #identifier]
+                || {
+                    let _: &_ = #expr;
+                };
+            });
+        }
+        self.generate_spec_item_fn_with_checks(
+            SpecItemType::Postcondition, SpecContext::PLEDGE, spec_id, rhs, checks, item
+        )
+    }
+
+    /// Generate a dummy function for checking the given assertion, which is
+    /// evaluated in `context`, with `checks` put in front of the type-checks
+    /// of the assertion. The body of each type condition of the assertion is
+    /// type-checked in a further function that additionally has its bounds,
+    /// so that they do not constrain the rest of the assertion.
+    fn generate_spec_item_fn_with_checks(
+        &mut self,
+        spec_type: SpecItemType,
+        context: SpecContext,
+        spec_id: untyped::SpecificationId,
+        assertion: untyped::Assertion,
+        checks: TokenStream,
+        item: &untyped::AnyFnItem,
+    ) -> syn::Result<Vec<syn::Item>> {
         if let Some(span) = self.check_contains_keyword_in_params(item, "result") {
             return Err(syn::Error::new(
                 span,
                 "it is not allowed to use the keyword `result` as a function argument".to_string(),
             ));
         }
+        self.check_contextual_functions(context, &assertion, param_names(item))?;
         let item_name = syn::Ident::new(
             &format!("prusti_{}_item_{}_{}", spec_type, item.sig().ident, spec_id),
             item.span(),
         );
-        let mut statements = TokenStream::new();
+        let mut statements = checks;
         assertion.encode_type_check(&mut statements);
         let spec_id_str = spec_id.to_string();
-        let assertion_json = crate::specifications::json::to_json_string(&assertion);
+        let assertion_json = crate::specifications::json::to_json_string(
+            &assertion, self.message(spec_id)
+        )?;
         let mut spec_item: syn::ItemFn = syn::parse_quote! {
             #[allow(unused_must_use, unused_variables)]
             #[prusti::spec_only]
@@ -128,20 +254,248 @@ impl AstRewriter {
             let fn_arg = self.generate_result_arg(item);
             spec_item.sig.inputs.push(fn_arg);
         }
+        let type_conditions = assertion.type_conditions();
+        let is_method = matches!(item.sig().inputs.first(), Some(syn::FnArg::Receiver(_)));
+        check_type_condition_params(&type_conditions, &item.sig().generics, is_method)?;
+        let mut spec_items = Vec::with_capacity(1 + type_conditions.len());
+        for condition in &type_conditions {
+            let condition_item_name = syn::Ident::new(
+                &format!("{}_{}", item_name, condition.vars.id),
+                item.span(),
+            );
+            let identifier = condition.identifier();
+            let statements = condition.encode_type_check();
+            let mut condition_item: syn::ItemFn = syn::parse_quote! {
+                #[allow(unused_must_use, unused_variables)]
+                #[prusti::spec_only]
+                #[prusti::type_cond = #identifier]
+                fn #condition_item_name() {
+                    #statements
+                }
+            };
+            condition_item.sig.generics = spec_item.sig.generics.clone();
+            condition_item.sig.generics.make_where_clause().predicates
+                .extend(condition.predicates.iter().cloned());
+            condition_item.sig.inputs = spec_item.sig.inputs.clone();
+            spec_items.push(syn::Item::Fn(condition_item));
+        }
+        spec_items.insert(0, syn::Item::Fn(spec_item));
+        Ok(spec_items)
+    }
+
+    /// Generate a dummy method that records that the precondition or the
+    /// postcondition (depending on `spec_type`) of the trait implementation
+    /// method `item` must be checked to refine the one of the method of
+    /// `trait_path` that it implements. The method calls the trait method
+    /// with its own parameters so that it gets resolved by the compiler and
+    /// the generic arguments are inferred. They are also given explicitly
+    /// unless the method has `impl Trait` parameters, which rule that out.
+    pub fn generate_spec_item_refinement(
+        &mut self,
+        spec_type: SpecItemType,
+        spec_id: untyped::SpecificationId,
+        trait_path: &syn::Path,
+        item: &untyped::AnyFnItem,
+    ) -> syn::Result<syn::Item> {
+        let method_ident = &item.sig().ident;
+        let item_name = syn::Ident::new(
+            &format!("prusti_{}_item_{}_{}", spec_type, method_ident, spec_id),
+            item.span(),
+        );
+        let spec_id_str = spec_id.to_string();
+        let obligation = match spec_type {
+            SpecItemType::PreconditionRefinement => "pre",
+            SpecItemType::PostconditionRefinement => "post",
+            _ => unreachable!("not a refinement: {}", spec_type),
+        };
+        let generics = &item.sig().generics;
+        let mut impl_trait_finder = ImplTraitFinder::default();
+        for input in &item.sig().inputs {
+            impl_trait_finder.visit_fn_arg(input);
+        }
+        let generic_args = generics.params.iter().filter_map(|param| match param {
+            syn::GenericParam::Type(param) => Some(param.ident.to_token_stream()),
+            syn::GenericParam::Const(param) => Some(param.ident.to_token_stream()),
+            syn::GenericParam::Lifetime(_) => None,
+        }).collect::<Vec<_>>();
+        let turbofish = if generic_args.is_empty() || impl_trait_finder.found {
+            TokenStream::new()
+        } else {
+            quote! { ::<#(#generic_args),*> }
+        };
+        // the parameters are renamed, because their patterns may not be
+        // usable as arguments
+        let mut inputs = item.sig().inputs.clone();
+        let mut args = vec![];
+        for (index, input) in inputs.iter_mut().enumerate() {
+            match input {
+                syn::FnArg::Receiver(_) => args.push(quote! { self }),
+                syn::FnArg::Typed(syn::PatType { pat: box syn::Pat::Ident(pat), .. })
+                    if pat.ident == "self" => args.push(quote! { self }),
+                syn::FnArg::Typed(pat_type) => {
+                    let arg = format_ident!("_prusti_arg_{}", index);
+                    *pat_type.pat = syn::parse_quote! { #arg };
+                    args.push(arg.to_token_stream());
+                }
+            }
+        }
+        let mut spec_item: syn::ItemFn = syn::parse2(quote_spanned! { item.span() =>
+            #[allow(unused_must_use, unused_variables, dead_code)]
+            #[prusti::spec_only]
+            #[prusti::spec_id = #spec_id_str]
+            #[prusti::subtyping_obligation = #obligation]
+            fn #item_name() {
+                let _ = <Self as #trait_path>::#method_ident #turbofish (#(#args),*);
+            }
+        })?;
+        spec_item.sig.generics = generics.clone();
+        spec_item.sig.inputs = inputs;
+        Ok(syn::Item::Fn(spec_item))
+    }
+
+    /// Generate a dummy method for checking the given type invariant.
+    ///
+    /// The method is put into an inherent impl block of the type so that
+    /// `self` is bound to the instance the invariant talks about. The body of
+    /// each type condition is type-checked in a further method of the block.
+    pub fn generate_spec_item_type_invariant(
+        &mut self,
+        spec_id: untyped::SpecificationId,
+        assertion: untyped::Assertion,
+        item: &untyped::AnyTypeItem,
+    ) -> syn::Result<syn::Item> {
+        let item_name = syn::Ident::new(
+            &format!("prusti_{}_item_{}_{}", SpecItemType::TypeInvariant, item.ident(), spec_id),
+            item.span(),
+        );
+        let mut statements = TokenStream::new();
+        assertion.encode_type_check(&mut statements);
+        let spec_id_str = spec_id.to_string();
+        let assertion_json = crate::specifications::json::to_json_string(
+            &assertion, self.message(spec_id)
+        )?;
+        let item_ident = item.ident();
+        let (impl_generics, ty_generics, where_clause) = item.generics().split_for_impl();
+        let condition_items = assertion.type_conditions().iter().map(|condition| {
+            let condition_item_name = syn::Ident::new(
+                &format!("{}_{}", item_name, condition.vars.id),
+                item.span(),
+            );
+            let identifier = condition.identifier();
+            let statements = condition.encode_type_check();
+            let predicates = &condition.predicates;
+            quote! {
+                #[allow(unused_must_use, unused_variables, dead_code, non_snake_case)]
+                #[prusti::spec_only]
+                #[prusti::type_cond = #identifier]
+                fn #condition_item_name(&self) where #(#predicates),* {
+                    #statements
+                }
+            }
+        }).collect::<Vec<_>>();
+        let spec_item: syn::ItemImpl = syn::parse_quote! {
+            impl #impl_generics #item_ident #ty_generics #where_clause {
+                #[allow(unused_must_use, unused_variables, dead_code, non_snake_case)]
+                #[prusti::spec_only]
+                #[prusti::spec_id = #spec_id_str]
+                #[prusti::assertion = #assertion_json]
+                fn #item_name(&self) {
+                    #statements
+                }
+                #(#condition_items)*
+            }
+        };
+        Ok(syn::Item::Impl(spec_item))
+    }
+
+    /// Generate a dummy function for checking the given termination measure.
+    pub fn generate_spec_item_variant(
+        &mut self,
+        spec_id: untyped::SpecificationId,
+        variant: untyped::Variant,
+        item: &untyped::AnyFnItem,
+    ) -> syn::Result<syn::Item> {
+        self.check_variant_contextual_functions(
+            SpecContext::of(SpecItemType::Variant), &variant, param_names(item)
+        )?;
+        let item_name = syn::Ident::new(
+            &format!("prusti_{}_item_{}_{}", SpecItemType::Variant, item.sig().ident, spec_id),
+            item.span(),
+        );
+        let mut statements = TokenStream::new();
+        variant.encode_type_check(&mut statements);
+        let spec_id_str = spec_id.to_string();
+        let variant_json = crate::specifications::json::variant_to_json_string(&variant)?;
+        let mut spec_item: syn::ItemFn = syn::parse_quote! {
+            #[allow(unused_must_use, unused_variables)]
+            #[prusti::spec_only]
+            #[prusti::spec_id = #spec_id_str]
+            #[prusti::variant = #variant_json]
+            fn #item_name() {
+                #statements
+            }
+        };
+        spec_item.sig.generics = item.sig().generics.clone();
+        spec_item.sig.inputs = item.sig().inputs.clone();
         Ok(syn::Item::Fn(spec_item))
     }
 
+    /// Check that the assertion contains no type conditions, which are only
+    /// supported in specifications of functions and types.
+    fn check_no_type_conditions(&self, assertion: &untyped::Assertion) -> syn::Result<()> {
+        if let Some(condition) = assertion.type_conditions().first() {
+            return Err(syn::Error::new(
+                condition.vars.span,
+                "type conditions are only supported in specifications of functions and types",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the assertion uses `old`, `before_expiry` and `result` only
+    /// where `context` gives them a meaning. `result` may be shadowed by the
+    /// `bound_variables`.
+    pub(crate) fn check_contextual_functions(
+        &self,
+        context: SpecContext,
+        assertion: &untyped::Assertion,
+        bound_variables: Vec<syn::Ident>,
+    ) -> syn::Result<()> {
+        let mut checker = ContextualFunctionChecker::new(context, bound_variables);
+        checker.check_assertion(assertion);
+        checker.finish()
+    }
+
+    /// Check the expressions of a termination measure like the assertions in
+    /// `check_contextual_functions`.
+    fn check_variant_contextual_functions(
+        &self,
+        context: SpecContext,
+        variant: &untyped::Variant,
+        bound_variables: Vec<syn::Ident>,
+    ) -> syn::Result<()> {
+        let mut checker = ContextualFunctionChecker::new(context, bound_variables);
+        for expr in &variant.exprs {
+            checker.visit_expr(&expr.expr);
+        }
+        checker.finish()
+    }
+
     /// Generate statements for checking the given loop invariant.
     pub fn generate_spec_loop(
         &mut self,
         spec_id: untyped::SpecificationId,
         assertion: untyped::Assertion,
-    ) -> TokenStream {
+    ) -> syn::Result<TokenStream> {
+        self.check_no_type_conditions(&assertion)?;
+        self.check_contextual_functions(SpecContext::LOOP, &assertion, vec![])?;
         let mut statements = TokenStream::new();
         assertion.encode_type_check(&mut statements);
         let spec_id_str = spec_id.to_string();
-        let assertion_json = crate::specifications::json::to_json_string(&assertion);
-        quote! {
+        let assertion_json = crate::specifications::json::to_json_string(
+            &assertion, self.message(spec_id)
+        )?;
+        Ok(quote! {
             #[allow(unused_must_use, unused_variables)]
             #[prusti::spec_only]
             #[prusti::loop_body_invariant_spec]
@@ -150,7 +504,30 @@ impl AstRewriter {
             || {
                 #statements
             };
-        }
+        })
+    }
+
+    /// Generate statements for checking the given loop variant.
+    pub fn generate_spec_loop_variant(
+        &mut self,
+        spec_id: untyped::SpecificationId,
+        variant: untyped::Variant,
+    ) -> syn::Result<TokenStream> {
+        self.check_variant_contextual_functions(SpecContext::LOOP, &variant, vec![])?;
+        let mut statements = TokenStream::new();
+        variant.encode_type_check(&mut statements);
+        let spec_id_str = spec_id.to_string();
+        let variant_json = crate::specifications::json::variant_to_json_string(&variant)?;
+        Ok(quote! {
+            #[allow(unused_must_use, unused_variables)]
+            #[prusti::spec_only]
+            #[prusti::loop_body_variant_spec]
+            #[prusti::spec_id = #spec_id_str]
+            #[prusti::variant = #variant_json]
+            || {
+                #statements
+            };
+        })
     }
 
     /// Generate statements for checking a closure specification.
@@ -159,12 +536,24 @@ impl AstRewriter {
         &mut self,
         preconds: Vec<(untyped::SpecificationId,untyped::Assertion)>,
         postconds: Vec<(untyped::SpecificationId,untyped::Assertion)>
-    ) -> (TokenStream, TokenStream) {
-        let process_cond = |suffix: &str, count: i32, id: &untyped::SpecificationId, assertion: &untyped::Assertion, ts: &mut TokenStream| {
+    ) -> syn::Result<(TokenStream, TokenStream)> {
+        for (_, assertion) in preconds.iter().chain(postconds.iter()) {
+            self.check_no_type_conditions(assertion)?;
+        }
+        for (_, assertion) in &preconds {
+            self.check_contextual_functions(SpecContext::CLOSURE_PRECONDITION, assertion, vec![])?;
+        }
+        for (_, assertion) in &postconds {
+            self.check_contextual_functions(SpecContext::CLOSURE_POSTCONDITION, assertion, vec![])?;
+        }
+
+        let process_cond = |suffix: &str, count: i32, id: &untyped::SpecificationId, assertion: &untyped::Assertion, ts: &mut TokenStream| -> syn::Result<()> {
             let spec_id_str = id.to_string();
             let mut encoded = TokenStream::new();
             assertion.encode_type_check(&mut encoded);
-            let assertion_json = crate::specifications::json::to_json_string(&assertion);
+            let assertion_json = crate::specifications::json::to_json_string(
+                &assertion, self.message(*id)
+            )?;
             let var_name = format_ident! ("_prusti_closure_{}{}", suffix, count.to_string());
             ts.extend(quote! {
                 #[prusti::spec_only]
@@ -175,20 +564,218 @@ impl AstRewriter {
                     #encoded
                 };
             });
+            Ok(())
         };
 
         let mut pre_ts = TokenStream::new ();
         let mut post_ts = TokenStream::new ();
         let mut count = 0;
         for (id, precond) in preconds {
-            process_cond (&"pre", count, &id, &precond, &mut pre_ts);
+            process_cond (&"pre", count, &id, &precond, &mut pre_ts)?;
         }
 
         count = 0;
         for (id, postcond) in postconds {
-            process_cond (&"post", count, &id, &postcond, &mut post_ts);
+            process_cond (&"post", count, &id, &postcond, &mut post_ts)?;
+        }
+
+        Ok((pre_ts, post_ts))
+    }
+}
+
+/// The names of the parameters of `item` that are bound to an identifier.
+fn param_names(item: &untyped::AnyFnItem) -> Vec<syn::Ident> {
+    item.sig().inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(syn::PatType { pat: box syn::Pat::Ident(pat), .. }) => Some(pat.ident.clone()),
+        _ => None,
+    }).collect()
+}
+
+/// Check that type conditions only bound type parameters of the specified
+/// item. The macros do not know the parameters of the impl or trait that a
+/// method belongs to, so bounds in specifications of methods (functions with
+/// a receiver) are not checked.
+pub(crate) fn check_type_condition_params(
+    conditions: &[untyped::TypeCondition],
+    generics: &syn::Generics,
+    is_method: bool,
+) -> syn::Result<()> {
+    if is_method {
+        return Ok(());
+    }
+    for condition in conditions {
+        for var in &condition.vars.vars {
+            if !generics.type_params().any(|param| param.ident == var.name) {
+                return Err(syn::Error::new(
+                    var.name.span(),
+                    format!("`{}` is not a type parameter of the specified item", var.name),
+                ));
+            }
         }
+    }
+    Ok(())
+}
+
+/// Rewrites paths `Self::X` to `<Self as Trait>::X` for the associated types
+/// and constants `X` of a trait implementation, so that the spec items of the
+/// implementation, which are put into an inherent impl block, can refer to
+/// them.
+///
+/// Only the items defined in the implementation are known, so constants with
+/// a default in the trait are left as they are. They still resolve when the
+/// trait is in scope, but qualifying every `Self::X` would break the inherent
+/// items of the type and the items of supertraits.
+pub(crate) struct AssociatedItemQualifier<'a> {
+    pub trait_path: &'a syn::Path,
+    pub associated_items: &'a [syn::Ident],
+}
+
+impl<'a> AssociatedItemQualifier<'a> {
+    fn qualify(&self, qself: &mut Option<syn::QSelf>, path: &mut syn::Path) {
+        let is_associated_item = qself.is_none()
+            && path.leading_colon.is_none()
+            && path.segments.len() >= 2
+            && path.segments[0].ident == "Self"
+            && self.associated_items.contains(&path.segments[1].ident);
+        if !is_associated_item {
+            return;
+        }
+        let span = path.segments[0].ident.span();
+        *qself = Some(syn::QSelf {
+            lt_token: syn::Token![<](span),
+            ty: box syn::Type::Path(syn::TypePath {
+                qself: None,
+                path: syn::Ident::new("Self", span).into(),
+            }),
+            position: self.trait_path.segments.len(),
+            as_token: Some(syn::Token![as](span)),
+            gt_token: syn::Token![>](span),
+        });
+        let mut qualified_path = self.trait_path.clone();
+        qualified_path.segments.extend(path.segments.iter().skip(1).cloned());
+        *path = qualified_path;
+    }
+}
+
+impl<'a> syn::visit_mut::VisitMut for AssociatedItemQualifier<'a> {
+    fn visit_type_path_mut(&mut self, type_path: &mut syn::TypePath) {
+        self.qualify(&mut type_path.qself, &mut type_path.path);
+        syn::visit_mut::visit_type_path_mut(self, type_path);
+    }
+
+    fn visit_expr_path_mut(&mut self, expr_path: &mut syn::ExprPath) {
+        self.qualify(&mut expr_path.qself, &mut expr_path.path);
+        syn::visit_mut::visit_expr_path_mut(self, expr_path);
+    }
+}
+
+/// Checks that an assertion only uses the contextual functions `old` and
+/// `before_expiry` and the variable `result` where they have a meaning.
+struct ContextualFunctionChecker {
+    context: SpecContext,
+    /// The variables that are in scope and may shadow `result`.
+    bound_variables: Vec<syn::Ident>,
+    error: Option<syn::Error>,
+}
+
+impl ContextualFunctionChecker {
+    fn new(context: SpecContext, bound_variables: Vec<syn::Ident>) -> Self {
+        Self { context, bound_variables, error: None }
+    }
+
+    fn report(&mut self, span: Span, message: &str) {
+        if self.error.is_none() {
+            self.error = Some(syn::Error::new(span, message));
+        }
+    }
+
+    fn check_assertion(&mut self, assertion: &untyped::Assertion) {
+        match &*assertion.kind {
+            untyped::AssertionKind::Expr(expr) => self.visit_expr(&expr.expr),
+            untyped::AssertionKind::And(assertions) | untyped::AssertionKind::Or(assertions) => {
+                for assertion in assertions {
+                    self.check_assertion(assertion);
+                }
+            }
+            untyped::AssertionKind::Not(assertion) => self.check_assertion(assertion),
+            untyped::AssertionKind::Implies(lhs, rhs) | untyped::AssertionKind::Iff(lhs, rhs) => {
+                self.check_assertion(lhs);
+                self.check_assertion(rhs);
+            }
+            untyped::AssertionKind::TypeCond(_, body) => self.check_assertion(body),
+            untyped::AssertionKind::ForAll(vars, triggers, body)
+            | untyped::AssertionKind::Exists(vars, triggers, body) => {
+                let count = self.bound_variables.len();
+                self.bound_variables.extend(vars.vars.iter().map(|var| var.name.clone()));
+                for trigger in &triggers.0 {
+                    for term in &trigger.0 {
+                        self.visit_expr(&term.expr);
+                    }
+                }
+                self.check_assertion(body);
+                self.bound_variables.truncate(count);
+            }
+        }
+    }
+
+    fn finish(self) -> syn::Result<()> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'ast> syn::visit::Visit<'ast> for ContextualFunctionChecker {
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(syn::ExprPath { qself: None, path, .. }) = &*call.func {
+            if path.is_ident("old") && !self.context.old {
+                self.report(
+                    path.span(),
+                    "`old` can only be used in postconditions, pledges and loop invariants",
+                );
+            }
+            if path.is_ident("before_expiry") && !self.context.before_expiry {
+                self.report(path.span(), "`before_expiry` can only be used in the body of a pledge");
+            }
+        }
+        syn::visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_path(&mut self, expr: &'ast syn::ExprPath) {
+        if let Some(ident) = expr.path.get_ident() {
+            let is_result = expr.qself.is_none() && ident == "result";
+            if is_result && !self.context.result && !self.bound_variables.contains(ident) {
+                self.report(ident.span(), "`result` can only be used in postconditions and pledges");
+            }
+        }
+        syn::visit::visit_expr_path(self, expr);
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+        let count = self.bound_variables.len();
+        for input in &closure.inputs {
+            match input {
+                syn::Pat::Ident(pat) => self.bound_variables.push(pat.ident.clone()),
+                syn::Pat::Type(syn::PatType { pat: box syn::Pat::Ident(pat), .. }) => {
+                    self.bound_variables.push(pat.ident.clone());
+                }
+                _ => {}
+            }
+        }
+        syn::visit::visit_expr_closure(self, closure);
+        self.bound_variables.truncate(count);
+    }
+}
+
+/// Finds `impl Trait` types, e.g. in the types of the parameters of a method.
+#[derive(Default)]
+struct ImplTraitFinder {
+    found: bool,
+}
 
-        (pre_ts, post_ts)
+impl<'ast> syn::visit::Visit<'ast> for ImplTraitFinder {
+    fn visit_type_impl_trait(&mut self, _: &'ast syn::TypeImplTrait) {
+        self.found = true;
     }
 }
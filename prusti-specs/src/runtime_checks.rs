@@ -0,0 +1,853 @@
+/// Translation of Prusti specifications into runtime checks, used by
+/// `prusti-contracts-impl` when the `runtime-checks` feature is enabled.
+///
+/// Preconditions, postconditions and loop body invariants are parsed with the
+/// same `Parser` as in a Prusti build and turned into `debug_assert!`s. The
+/// body of a function with a postcondition is evaluated in a labelled loop,
+/// out of which its `return`s break, so that its result can be bound to
+/// `result` and checked before being returned. Arguments of `old(...)` are
+/// snapshotted via `Clone` on entry. So are the places of by-value parameters
+/// that postconditions mention, e.g. `x.0`, if their types implement `Clone`;
+/// postconditions mentioning other places of by-value parameters are not
+/// checked.
+///
+/// Quantifiers whose bodies bound each quantified variable from both sides,
+/// for example `forall(|i: usize| 0 <= i && i < n ==> P)` or
+/// `exists(|i: usize| 0 <= i && i < n && P)`, are checked by iterating over
+/// the ranges. Variables of unsigned integer types are bounded from below by
+/// zero if no lower bound is given. Unbounded quantifiers are reported as a
+/// deprecation warning or skipped, depending on the `UnboundedQuantifierPolicy`.
+///
+/// Parts of assertions that cannot be evaluated at runtime (unbounded
+/// quantifiers and type conditions) are not checked. A conjunction skips such
+/// conjuncts, while any other assertion containing them is not checked at all.
+
+use crate::extract_prusti_attributes;
+use crate::specifications::common::{AssertionKind, ForAllVars};
+use crate::specifications::preparser::{Arg, AssertionWithoutId, Parser};
+use crate::specifications::untyped;
+use crate::SpecAttributeKind;
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::visit_mut::VisitMut;
+
+/// What to do with quantifiers that cannot be checked because they do not
+/// bound their variables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnboundedQuantifierPolicy {
+    /// Do not check the quantifier and emit a warning at compile time.
+    Warn,
+    /// Silently do not check the quantifier.
+    Skip,
+}
+
+/// Rewrite a function so that it checks *all* its `requires` and `ensures`
+/// attributes at runtime. The remaining specification attributes have no
+/// runtime semantics and are dropped.
+///
+/// The first attribute (the outer one) needs to be passed via `attr_kind` and `attr` because
+/// the compiler executes it as as a procedural macro attribute.
+pub fn insert_runtime_checks(
+    outer_attr_kind: SpecAttributeKind,
+    outer_attr_tokens: TokenStream,
+    item_tokens: TokenStream,
+    policy: UnboundedQuantifierPolicy,
+) -> TokenStream {
+    match try_insert_runtime_checks(outer_attr_kind, outer_attr_tokens, item_tokens, policy) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn try_insert_runtime_checks(
+    outer_attr_kind: SpecAttributeKind,
+    outer_attr_tokens: TokenStream,
+    item_tokens: TokenStream,
+    policy: UnboundedQuantifierPolicy,
+) -> syn::Result<TokenStream> {
+    let mut item: untyped::AnyFnItem = syn::parse2(item_tokens)?;
+
+    // Start with the outer attribute
+    let mut prusti_attributes = vec![
+        (outer_attr_kind, outer_attr_tokens)
+    ];
+
+    // Collect the remaining Prusti attributes, removing them from `item`.
+    prusti_attributes.extend(extract_prusti_attributes(item.attrs_mut()));
+
+    let mut preconditions = vec![];
+    let mut postconditions = vec![];
+    for (attr_kind, attr_tokens) in prusti_attributes {
+        match attr_kind {
+            SpecAttributeKind::Requires => preconditions.push(Clause::parse(attr_tokens)?),
+            SpecAttributeKind::Ensures => postconditions.push(Clause::parse(attr_tokens)?),
+            SpecAttributeKind::Predicate => return Err(syn::Error::new(
+                item.sig().ident.span(),
+                "predicates cannot have other specifications",
+            )),
+            _ => {}
+        }
+    }
+
+    let (sig, block) = match &mut item {
+        untyped::AnyFnItem::Fn(item) => (&item.sig, &mut *item.block),
+        untyped::AnyFnItem::ImplMethod(item) => (&item.sig, &mut item.block),
+        untyped::AnyFnItem::TraitMethod(item) => match &mut item.default {
+            Some(block) => (&item.sig, block),
+            // a required trait method has no body to check
+            None => return Ok(item.into_token_stream()),
+        },
+    };
+    let mut translator = Translator::new(policy);
+    instrument_body(sig, block, &preconditions, &postconditions, &mut translator)?;
+    Ok(item.into_token_stream())
+}
+
+/// Generate the runtime check of a loop body invariant.
+pub fn body_invariant(tokens: TokenStream, policy: UnboundedQuantifierPolicy) -> TokenStream {
+    let clause = match Clause::parse(tokens) {
+        Ok(clause) => clause,
+        Err(err) => return err.to_compile_error(),
+    };
+    let mut translator = Translator::new(policy);
+    let check = match translator.translate(&clause.assertion) {
+        Some(check) => {
+            let message = clause.failure_message("loop body invariant");
+            quote! { debug_assert!(#check, "{}", #message) }
+        }
+        None => quote! { () },
+    };
+    let warnings = translator.warnings;
+    quote! {
+        {
+            #warnings
+            #check
+        }
+    }
+}
+
+/// Replace the assertion of a `#[predicate]` function by its runtime check,
+/// which is its body outside of Prusti.
+pub fn predicate(
+    attr: TokenStream,
+    item_tokens: TokenStream,
+    policy: UnboundedQuantifierPolicy,
+) -> TokenStream {
+    let untyped::PredicateFnItem { mut item, assertion } = match untyped::PredicateFnItem::parse(
+        attr, item_tokens
+    ) {
+        Ok(item) => item,
+        Err(err) => return err.to_compile_error(),
+    };
+    item.block = match predicate_body(assertion, policy) {
+        Ok(block) => block,
+        Err(err) => return err.to_compile_error(),
+    };
+    quote! {
+        #[allow(unused_variables, dead_code)]
+        #item
+    }
+}
+
+/// The body of a predicate that evaluates its assertion. If the assertion
+/// cannot be evaluated at runtime, the predicate is assumed to hold.
+pub(crate) fn predicate_body(
+    assertion: TokenStream,
+    policy: UnboundedQuantifierPolicy,
+) -> syn::Result<Box<syn::Block>> {
+    let clause = Clause::parse(assertion)?;
+    let mut translator = Translator::new(policy);
+    let check = translator.translate(&clause.assertion).unwrap_or_else(|| quote! { true });
+    let warnings = translator.warnings;
+    Ok(syn::parse_quote! {{
+        #warnings
+        #check
+    }})
+}
+
+/// A single `requires`, `ensures` or `body_invariant!` clause.
+struct Clause {
+    /// The source text of the clause, used in the failure message.
+    text: String,
+    /// The message given by the user, used in the failure message.
+    message: Option<String>,
+    assertion: AssertionWithoutId,
+}
+
+impl Clause {
+    fn parse(tokens: TokenStream) -> syn::Result<Self> {
+        let tokens = strip_parentheses(tokens);
+        let mut parser = Parser::from_token_stream(tokens.clone());
+        let message = parser.extract_message()?;
+        let text = match &message {
+            // the text without the message, which may be in parentheses
+            Some(_) => {
+                let mut tokens: Vec<_> = strip_parentheses(tokens).into_iter().collect();
+                tokens.truncate(tokens.len() - 2);
+                tokens.into_iter().collect::<TokenStream>().to_string()
+            }
+            None => tokens.to_string(),
+        };
+        let assertion = parser.extract_assertion()?;
+        Ok(Self { text, message, assertion })
+    }
+
+    /// The message of a failed runtime check of the clause, e.g.
+    /// ``precondition violated: x is positive (`x > 0`)``.
+    fn failure_message(&self, kind: &str) -> String {
+        match &self.message {
+            Some(message) => format!("{} violated: {} (`{}`)", kind, message, self.text),
+            None => format!("{} violated: `{}`", kind, self.text),
+        }
+    }
+}
+
+/// Attributes collected from the item still contain the parentheses around
+/// their arguments.
+fn strip_parentheses(tokens: TokenStream) -> TokenStream {
+    let mut iter = tokens.clone().into_iter();
+    match (iter.next(), iter.next()) {
+        (Some(TokenTree::Group(group)), None) if group.delimiter() == Delimiter::Parenthesis => {
+            group.stream()
+        }
+        _ => tokens,
+    }
+}
+
+/// Translates assertions into boolean Rust expressions.
+struct Translator {
+    policy: UnboundedQuantifierPolicy,
+    /// Statements that report the unbounded quantifiers found so far.
+    warnings: TokenStream,
+}
+
+impl Translator {
+    fn new(policy: UnboundedQuantifierPolicy) -> Self {
+        Self { policy, warnings: TokenStream::new() }
+    }
+
+    /// Translate the assertion, if it can be evaluated at runtime.
+    fn translate(&mut self, assertion: &AssertionWithoutId) -> Option<TokenStream> {
+        match &*assertion.kind {
+            AssertionKind::Expr(expression) => {
+                let expr = &expression.expr;
+                Some(quote! { (#expr) })
+            }
+            AssertionKind::And(conjuncts) => {
+                let checks: Vec<_> = conjuncts.iter()
+                    .filter_map(|conjunct| self.translate(conjunct))
+                    .collect();
+                if checks.is_empty() {
+                    None
+                } else {
+                    Some(quote! { (#(#checks)&&*) })
+                }
+            }
+            AssertionKind::Or(disjuncts) => {
+                let checks = disjuncts.iter()
+                    .map(|disjunct| self.translate(disjunct))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(quote! { (#(#checks)||*) })
+            }
+            AssertionKind::Not(assertion) => {
+                let check = self.translate(assertion)?;
+                Some(quote! { (!#check) })
+            }
+            AssertionKind::Implies(lhs, rhs) => {
+                let lhs = self.translate(lhs)?;
+                let rhs = self.translate(rhs)?;
+                Some(quote! { (!#lhs || #rhs) })
+            }
+            AssertionKind::Iff(lhs, rhs) => {
+                let lhs = self.translate(lhs)?;
+                let rhs = self.translate(rhs)?;
+                Some(quote! { (#lhs == #rhs) })
+            }
+            AssertionKind::TypeCond(..) => None,
+            AssertionKind::ForAll(vars, _, body) => {
+                // the body must have the shape `BOUNDS && A ==> A`
+                let (conditions, body) = match &*body.kind {
+                    AssertionKind::Implies(lhs, rhs) => (conjuncts(lhs), Some(rhs)),
+                    _ => (vec![], None),
+                };
+                self.translate_quantifier(vars, conditions, body, quote! { all })
+            }
+            AssertionKind::Exists(vars, _, body) => {
+                // the body must have the shape `BOUNDS && A`
+                self.translate_quantifier(vars, conjuncts(body), None, quote! { any })
+            }
+        }
+    }
+
+    /// Translate a quantifier into iterations over the ranges of its
+    /// variables, if `conditions` bound each of them from both sides.
+    fn translate_quantifier(
+        &mut self,
+        vars: &ForAllVars<(), Arg>,
+        conditions: Vec<&AssertionWithoutId>,
+        body: Option<&AssertionWithoutId>,
+        method: TokenStream,
+    ) -> Option<TokenStream> {
+        let mut ranges: Vec<Range> = vars.vars.iter().map(|_| Range::default()).collect();
+        let mut filters = vec![];
+        for condition in conditions {
+            if !self.add_bound(&vars.vars, &mut ranges, condition) {
+                filters.push(condition);
+            }
+        }
+        // unsigned integers are implicitly bounded from below by zero
+        for (var, range) in vars.vars.iter().zip(ranges.iter_mut()) {
+            if range.start.is_none() && is_unsigned_integer(&var.typ) {
+                range.start = Some((quote! { 0 }, false));
+            }
+        }
+        if ranges.iter().any(|range| range.start.is_none() || range.end.is_none()) {
+            self.report_unbounded_quantifier(vars);
+            return None;
+        }
+
+        let filters = filters.into_iter()
+            .map(|filter| self.translate(filter))
+            .collect::<Option<Vec<_>>>()?;
+        let mut check = match body {
+            // `forall`: the remaining conditions imply the body
+            Some(body) => {
+                let body = self.translate(body)?;
+                if filters.is_empty() {
+                    body
+                } else {
+                    quote! { (!(#(#filters)&&*) || #body) }
+                }
+            }
+            // `exists`: all remaining conditions hold
+            None if filters.is_empty() => quote! { true },
+            None => quote! { (#(#filters)&&*) },
+        };
+        for (var, range) in vars.vars.iter().zip(ranges).rev() {
+            let Arg { name, typ } = var;
+            let (start, exclusive) = range.start.unwrap();
+            let (end, inclusive) = range.end.unwrap();
+            let limits = if inclusive { quote! { ..= } } else { quote! { .. } };
+            // skipping the start of an exclusive range instead of starting
+            // at its successor cannot overflow
+            let skip = if exclusive { quote! { .skip(1) } } else { quote! {} };
+            check = quote! { ((#start) #limits (#end))#skip.#method(|#name: #typ| #check) };
+        }
+        Some(check)
+    }
+
+    /// If the condition is a bound of one of the quantified variables that
+    /// is not bounded from that side yet, record it and return `true`.
+    fn add_bound(
+        &self,
+        vars: &[Arg],
+        ranges: &mut [Range],
+        condition: &AssertionWithoutId,
+    ) -> bool {
+        let (left, op, right) = match &*condition.kind {
+            AssertionKind::Expr(expression) => match strip_parens(&expression.expr) {
+                syn::Expr::Binary(syn::ExprBinary { left, op, right, .. }) => (left, op, right),
+                _ => return false,
+            },
+            _ => return false,
+        };
+        for (index, (var, range)) in vars.iter().zip(ranges.iter_mut()).enumerate() {
+            let (bound, variable_on_left) = if is_variable(left, &var.name) {
+                (right, true)
+            } else if is_variable(right, &var.name) {
+                (left, false)
+            } else {
+                continue;
+            };
+            // the range of a variable can only depend on the variables that
+            // are quantified before it
+            let later_variables: Vec<_> = vars[index..].iter()
+                .map(|var| var.name.clone())
+                .collect();
+            let mut finder = VariableFinder { variables: &later_variables, found: false };
+            finder.visit_expr_mut(&mut bound.as_ref().clone());
+            if finder.found {
+                continue;
+            }
+            match (op, variable_on_left) {
+                (syn::BinOp::Ge(_), true) | (syn::BinOp::Le(_), false)
+                    if range.start.is_none() => {
+                    range.start = Some((quote! { #bound }, false));
+                }
+                (syn::BinOp::Gt(_), true) | (syn::BinOp::Lt(_), false)
+                    if range.start.is_none() => {
+                    range.start = Some((quote! { #bound }, true));
+                }
+                (syn::BinOp::Lt(_), true) | (syn::BinOp::Gt(_), false)
+                    if range.end.is_none() => {
+                    range.end = Some((quote! { #bound }, false));
+                }
+                (syn::BinOp::Le(_), true) | (syn::BinOp::Ge(_), false)
+                    if range.end.is_none() => {
+                    range.end = Some((quote! { #bound }, true));
+                }
+                _ => continue,
+            }
+            return true;
+        }
+        false
+    }
+
+    fn report_unbounded_quantifier(&mut self, vars: &ForAllVars<(), Arg>) {
+        if self.policy == UnboundedQuantifierPolicy::Skip {
+            return;
+        }
+        // proc macros cannot emit warnings on stable, so use a deprecated
+        // item, in a block of its own so that it can be reported repeatedly
+        let span = vars.vars.first().map_or_else(Span::call_site, |var| var.name.span());
+        self.warnings.extend(quote_spanned! { span =>
+            {
+                #[deprecated(note = "the quantifier does not bound its variables \
+                    (`lo <= i && i < hi`) and is not checked at runtime")]
+                #[allow(non_camel_case_types)]
+                struct unbounded_quantifier;
+                let _ = unbounded_quantifier;
+            }
+        });
+    }
+}
+
+/// The range of a quantified variable.
+#[derive(Default)]
+struct Range {
+    /// The start and whether it is exclusive.
+    start: Option<(TokenStream, bool)>,
+    /// The end and whether it is inclusive.
+    end: Option<(TokenStream, bool)>,
+}
+
+/// The members of a conjunction, or the assertion itself.
+fn conjuncts(assertion: &AssertionWithoutId) -> Vec<&AssertionWithoutId> {
+    match &*assertion.kind {
+        AssertionKind::And(members) => members.iter().flat_map(conjuncts).collect(),
+        _ => vec![assertion],
+    }
+}
+
+fn strip_parens(expr: &syn::Expr) -> &syn::Expr {
+    match expr {
+        syn::Expr::Paren(syn::ExprParen { expr, .. }) => strip_parens(expr),
+        _ => expr,
+    }
+}
+
+fn is_unsigned_integer(typ: &syn::Type) -> bool {
+    match typ {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => {
+            ["u8", "u16", "u32", "u64", "u128", "usize"].iter().any(|name| path.is_ident(name))
+        }
+        _ => false,
+    }
+}
+
+fn is_variable(expr: &syn::Expr, var: &syn::Ident) -> bool {
+    match strip_parens(expr) {
+        syn::Expr::Path(syn::ExprPath { qself: None, path, .. }) => path.is_ident(var),
+        _ => false,
+    }
+}
+
+/// Replaces each `old(e)` by a variable that holds a snapshot of `e` taken
+/// on entry of the function. If `e` mentions a quantified variable, each of
+/// the other variables in `e` is snapshotted instead.
+#[derive(Default)]
+struct OldSnapshots {
+    snapshots: Vec<(syn::Ident, syn::Expr)>,
+    /// The parameters of the enclosing closures, which are generated for
+    /// quantifiers.
+    quantified_variables: Vec<syn::Ident>,
+}
+
+impl OldSnapshots {
+    /// Snapshot `expr`, reusing the snapshot of an identical expression.
+    fn snapshot(&mut self, expr: syn::Expr) -> syn::Expr {
+        let tokens = expr.to_token_stream().to_string();
+        let existing = self.snapshots.iter()
+            .find(|(_, snapshot)| snapshot.to_token_stream().to_string() == tokens);
+        let ident = match existing {
+            Some((ident, _)) => ident.clone(),
+            None => {
+                let ident = format_ident!("_prusti_old_{}", self.snapshots.len());
+                self.snapshots.push((ident.clone(), expr));
+                ident
+            }
+        };
+        syn::parse_quote! { #ident }
+    }
+}
+
+impl VisitMut for OldSnapshots {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if let syn::Expr::Call(syn::ExprCall { func: box syn::Expr::Path(path), args, .. }) = expr {
+            if path.qself.is_none() && path.path.is_ident("old") && args.len() == 1 {
+                let mut arg = args[0].clone();
+                let mut finder = VariableFinder {
+                    variables: &self.quantified_variables,
+                    found: false,
+                };
+                finder.visit_expr_mut(&mut arg.clone());
+                *expr = if finder.found {
+                    FreeVariableSnapshots(self).visit_expr_mut(&mut arg);
+                    arg
+                } else {
+                    self.snapshot(arg)
+                };
+                return;
+            }
+        }
+        if let syn::Expr::Closure(closure) = expr {
+            let count = self.quantified_variables.len();
+            for input in &closure.inputs {
+                if let syn::Pat::Type(syn::PatType { pat: box syn::Pat::Ident(pat), .. }) = input {
+                    self.quantified_variables.push(pat.ident.clone());
+                }
+            }
+            syn::visit_mut::visit_expr_mut(self, expr);
+            self.quantified_variables.truncate(count);
+            return;
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// Replaces each variable that is not quantified by a snapshot.
+struct FreeVariableSnapshots<'a>(&'a mut OldSnapshots);
+
+impl<'a> VisitMut for FreeVariableSnapshots<'a> {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if let syn::Expr::Path(syn::ExprPath { qself: None, path, .. }) = expr {
+            if let Some(ident) = path.get_ident() {
+                if !self.0.quantified_variables.contains(ident) {
+                    *expr = self.0.snapshot(expr.clone());
+                }
+                return;
+            }
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// Checks whether an expression mentions one of the given variables.
+struct VariableFinder<'a> {
+    variables: &'a [syn::Ident],
+    found: bool,
+}
+
+impl<'a> VisitMut for VariableFinder<'a> {
+    fn visit_expr_path_mut(&mut self, path: &mut syn::ExprPath) {
+        if let Some(ident) = path.path.get_ident() {
+            self.found |= self.variables.contains(ident);
+        }
+    }
+}
+
+/// Replaces the places rooted at by-value parameters that a postcondition
+/// mentions outside of `old(..)`, e.g. `x` or `x.0`, by snapshots taken on
+/// entry of the function, because the postcondition refers to their values
+/// on entry, while the body may have changed or moved them. A snapshot is
+/// `None` if the type of the place does not implement `Clone`, in which case
+/// the postcondition is not checked.
+struct ParameterSnapshots<'a> {
+    parameters: &'a [syn::Ident],
+    /// The snapshot variables and their places.
+    snapshots: Vec<(syn::Ident, syn::Expr)>,
+    /// The snapshot variables used by the current postcondition.
+    used: Vec<syn::Ident>,
+    /// The parameters of the enclosing closures, which are generated for
+    /// quantifiers and may shadow the parameters of the function.
+    quantified_variables: Vec<syn::Ident>,
+}
+
+impl<'a> ParameterSnapshots<'a> {
+    fn new(parameters: &'a [syn::Ident]) -> Self {
+        Self { parameters, snapshots: vec![], used: vec![], quantified_variables: vec![] }
+    }
+
+    /// The parameter that `expr` is a place of, if it is a field of a field
+    /// ... of a by-value parameter.
+    fn root_parameter(&self, expr: &syn::Expr) -> Option<syn::Ident> {
+        match expr {
+            syn::Expr::Field(syn::ExprField { base, .. }) => self.root_parameter(base),
+            syn::Expr::Path(syn::ExprPath { qself: None, path, .. }) => path.get_ident()
+                .filter(|ident| {
+                    self.parameters.contains(ident) && !self.quantified_variables.contains(ident)
+                })
+                .cloned(),
+            _ => None,
+        }
+    }
+
+    /// Snapshot the place, reusing the snapshot of an identical place.
+    fn snapshot(&mut self, place: syn::Expr) -> syn::Expr {
+        let tokens = place.to_token_stream().to_string();
+        let existing = self.snapshots.iter()
+            .find(|(_, snapshot)| snapshot.to_token_stream().to_string() == tokens);
+        let ident = match existing {
+            Some((ident, _)) => ident.clone(),
+            None => {
+                let ident = format_ident!("_prusti_param_{}", self.snapshots.len());
+                self.snapshots.push((ident.clone(), place));
+                ident
+            }
+        };
+        if !self.used.contains(&ident) {
+            self.used.push(ident.clone());
+        }
+        // the snapshot is bound by reference when the postcondition is checked
+        syn::parse_quote! { (*#ident) }
+    }
+
+    /// Check `check` if the snapshots that it uses could be taken.
+    fn guard(&mut self, check: syn::Expr) -> TokenStream {
+        let used = std::mem::take(&mut self.used);
+        if used.is_empty() {
+            return check.into_token_stream();
+        }
+        quote! {
+            match (#(&#used,)*) {
+                (#(::core::option::Option::Some(#used),)*) => #check,
+                _ => true,
+            }
+        }
+    }
+}
+
+impl<'a> VisitMut for ParameterSnapshots<'a> {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        match expr {
+            syn::Expr::Call(syn::ExprCall { func: box syn::Expr::Path(path), .. })
+                if path.qself.is_none() && path.path.is_ident("old") => return,
+            syn::Expr::Field(_) | syn::Expr::Path(_) if self.root_parameter(expr).is_some() => {
+                *expr = self.snapshot(expr.clone());
+                return;
+            }
+            syn::Expr::Closure(closure) => {
+                let count = self.quantified_variables.len();
+                for input in &closure.inputs {
+                    if let syn::Pat::Type(syn::PatType { pat: box syn::Pat::Ident(pat), .. }) = input {
+                        self.quantified_variables.push(pat.ident.clone());
+                    }
+                }
+                syn::visit_mut::visit_expr_mut(self, expr);
+                self.quantified_variables.truncate(count);
+                return;
+            }
+            _ => {}
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// The items with which `ParameterSnapshots` clones places whose type
+/// implements `Clone`, and gives up on the others. The method is resolved to
+/// the impl of `PrustiCloneSnapshot` if `T: Clone` holds, and otherwise to
+/// the one of `PrustiNoSnapshot`, which needs an additional autoref.
+fn snapshot_items() -> TokenStream {
+    quote! {
+        struct PrustiSnapshot<'a, T>(&'a T);
+        trait PrustiCloneSnapshot<T> {
+            fn prusti_snapshot(&self) -> ::core::option::Option<T>;
+        }
+        impl<'a, T: ::core::clone::Clone> PrustiCloneSnapshot<T> for PrustiSnapshot<'a, T> {
+            fn prusti_snapshot(&self) -> ::core::option::Option<T> {
+                ::core::option::Option::Some(::core::clone::Clone::clone(self.0))
+            }
+        }
+        trait PrustiNoSnapshot<T> {
+            fn prusti_snapshot(&self) -> ::core::option::Option<T>;
+        }
+        impl<'a, 'b, T> PrustiNoSnapshot<T> for &'b PrustiSnapshot<'a, T> {
+            fn prusti_snapshot(&self) -> ::core::option::Option<T> {
+                ::core::option::Option::None
+            }
+        }
+    }
+}
+
+/// The parameters of the function that are passed by value, i.e. not by
+/// reference.
+fn by_value_parameters(sig: &syn::Signature) -> Vec<syn::Ident> {
+    sig.inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Receiver(receiver) if receiver.reference.is_none() => {
+            Some(format_ident!("self"))
+        }
+        syn::FnArg::Typed(syn::PatType { pat: box syn::Pat::Ident(pat), ty, .. })
+            if pat.by_ref.is_none() && !matches!(**ty, syn::Type::Reference(_)) => {
+            Some(pat.ident.clone())
+        }
+        _ => None,
+    }).collect()
+}
+
+/// The label of the loop that the body of an instrumented function is
+/// wrapped in, so that leaving the body does not skip the postconditions.
+fn body_label() -> syn::Lifetime {
+    syn::Lifetime::new("'prusti_body", Span::call_site())
+}
+
+/// How the `?` operator returns from the function.
+#[derive(Clone, Copy)]
+enum TryReturn {
+    Result,
+    Option,
+}
+
+/// Replaces `return` in the body of a function by a `break` out of the loop
+/// labelled with `body_label`. If the function returns a `Result` or an
+/// `Option`, the `?` operator is desugared in the same way; for other return
+/// types it is left alone and skips the postconditions, as do returns in
+/// macro invocations. Closures, async blocks and nested items are not
+/// entered, since their `return` refers to themselves.
+struct ReturnRewriter {
+    label: syn::Lifetime,
+    try_return: Option<TryReturn>,
+}
+
+impl ReturnRewriter {
+    fn new(sig: &syn::Signature) -> Self {
+        let try_return = match &sig.output {
+            syn::ReturnType::Type(_, box syn::Type::Path(syn::TypePath { path, .. })) => {
+                match path.segments.last() {
+                    Some(segment) if segment.ident == "Result" => Some(TryReturn::Result),
+                    Some(segment) if segment.ident == "Option" => Some(TryReturn::Option),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        Self { label: body_label(), try_return }
+    }
+}
+
+impl VisitMut for ReturnRewriter {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if let syn::Expr::Closure(_) | syn::Expr::Async(_) = expr {
+            return;
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+        let label = &self.label;
+        match expr {
+            syn::Expr::Return(syn::ExprReturn { expr: value, .. }) => {
+                let value = value.as_ref().map_or_else(|| quote! { () }, ToTokens::to_token_stream);
+                *expr = syn::parse_quote! { break #label #value };
+            }
+            syn::Expr::Try(syn::ExprTry { expr: operand, .. }) => match self.try_return {
+                Some(TryReturn::Result) => {
+                    *expr = syn::parse_quote! {
+                        match #operand {
+                            ::core::result::Result::Ok(value) => value,
+                            ::core::result::Result::Err(error) => break #label
+                                ::core::result::Result::Err(::core::convert::From::from(error)),
+                        }
+                    };
+                }
+                Some(TryReturn::Option) => {
+                    *expr = syn::parse_quote! {
+                        match #operand {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => break #label ::core::option::Option::None,
+                        }
+                    };
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn visit_item_mut(&mut self, _item: &mut syn::Item) {}
+}
+
+fn instrument_body(
+    sig: &syn::Signature,
+    block: &mut syn::Block,
+    preconditions: &[Clause],
+    postconditions: &[Clause],
+    translator: &mut Translator,
+) -> syn::Result<()> {
+    // the body of a `const` function cannot contain `debug_assert!`s
+    if sig.constness.is_some() {
+        return Ok(());
+    }
+
+    let mut pre_checks = TokenStream::new();
+    for clause in preconditions {
+        if let Some(check) = translator.translate(&clause.assertion) {
+            let message = clause.failure_message("precondition");
+            pre_checks.extend(quote! { debug_assert!(#check, "{}", #message); });
+        }
+    }
+
+    let parameters = by_value_parameters(sig);
+    let mut parameter_snapshots = ParameterSnapshots::new(&parameters);
+    let mut old_snapshots = OldSnapshots::default();
+    let mut post_checks = TokenStream::new();
+    for clause in postconditions {
+        if let Some(check) = translator.translate(&clause.assertion) {
+            let mut check: syn::Expr = syn::parse2(check)?;
+            parameter_snapshots.visit_expr_mut(&mut check);
+            old_snapshots.visit_expr_mut(&mut check);
+            let check = parameter_snapshots.guard(check);
+            let message = clause.failure_message("postcondition");
+            post_checks.extend(quote! {
+                #[allow(unreachable_code)]
+                debug_assert!(#check, "{}", #message);
+            });
+        }
+    }
+
+    let warnings = &translator.warnings;
+    if post_checks.is_empty() {
+        if !pre_checks.is_empty() || !warnings.is_empty() {
+            // the statements are spliced in, since nesting the body as a
+            // block would lint as unnecessary braces in the user's code
+            let statements = &block.stmts;
+            *block = syn::parse_quote! {{
+                #warnings
+                #pre_checks
+                #(#statements)*
+            }};
+        }
+        return Ok(());
+    }
+
+    let mut snapshots = TokenStream::new();
+    if !parameter_snapshots.snapshots.is_empty() {
+        snapshots.extend(snapshot_items());
+    }
+    for (ident, place) in &parameter_snapshots.snapshots {
+        snapshots.extend(quote! { let #ident = (&PrustiSnapshot(&(#place))).prusti_snapshot(); });
+    }
+    for (ident, expr) in &old_snapshots.snapshots {
+        snapshots.extend(quote! { let #ident = (#expr).clone(); });
+    }
+    let result_type = match &sig.output {
+        syn::ReturnType::Default => quote! { : () },
+        // the type of a variable cannot be `impl Trait`
+        syn::ReturnType::Type(_, box syn::Type::ImplTrait(_)) => quote! {},
+        syn::ReturnType::Type(_, ty) => quote! { : #ty },
+    };
+    let mut body = block.clone();
+    let mut return_rewriter = ReturnRewriter::new(sig);
+    return_rewriter.visit_block_mut(&mut body);
+    let label = &return_rewriter.label;
+    *block = syn::parse_quote! {{
+        #warnings
+        #pre_checks
+        #snapshots
+        // the body is evaluated in place, so that the postconditions can
+        // refer to the arguments that it borrows; returns leave the loop
+        #[allow(unreachable_code, unused_braces)]
+        let result #result_type = #label: loop {
+            break #label #body;
+        };
+        #post_checks
+        result
+    }};
+    Ok(())
+}
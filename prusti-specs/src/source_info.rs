@@ -0,0 +1,66 @@
+//! Information about the source code of specifications that is only
+//! available when running as a procedural macro inside the compiler, namely
+//! the file and the original text of a span.
+//!
+//! Since `proc_macro` must not be used outside of procedural macros (for
+//! example when testing this crate), the procedural macros have to enable
+//! this information explicitly by calling `enable`.
+
+use proc_macro2::Span;
+use std::cell::Cell;
+
+thread_local! {
+    static ENABLED: Cell<bool> = Cell::new(false);
+}
+
+/// Record the file and the source text of specifications. Must only be
+/// called from a procedural macro.
+pub fn enable() {
+    ENABLED.with(|enabled| enabled.set(true));
+}
+
+fn compiler_span(span: Span) -> Option<proc_macro::Span> {
+    if is_enabled() {
+        Some(span.unwrap())
+    } else {
+        None
+    }
+}
+
+/// The path of the file that contains `span`, if it is a real file.
+pub(crate) fn file(span: Span) -> Option<String> {
+    let source_file = compiler_span(span)?.source_file();
+    if source_file.is_real() {
+        Some(source_file.path().display().to_string())
+    } else {
+        None
+    }
+}
+
+/// The original text of the source code at `span`.
+pub(crate) fn source_text(span: Span) -> Option<String> {
+    compiler_span(span)?.source_text()
+}
+
+/// Whether the procedural macros run inside the compiler, which expands the
+/// macros of a single crate.
+pub(crate) fn is_enabled() -> bool {
+    ENABLED.with(|enabled| enabled.get())
+}
+
+/// The path of the file that contains `span` relative to the directory of
+/// the crate, so that it does not depend on where the crate is built. Empty
+/// outside of the compiler.
+pub(crate) fn crate_file(span: Span) -> String {
+    let file = match file(span) {
+        Some(file) => file,
+        None => return String::new(),
+    };
+    match std::env::var("CARGO_MANIFEST_DIR") {
+        Ok(dir) => match std::path::Path::new(&file).strip_prefix(&dir) {
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => file,
+        },
+        Err(_) => file,
+    }
+}
@@ -9,6 +9,9 @@ pub enum SpecAttributeKind {
     AfterExpiryIf,
     Pure,
     Trusted,
+    Invariant,
+    Predicate,
+    Decreases,
 }
 
 impl TryFrom<String> for SpecAttributeKind {
@@ -22,6 +25,9 @@ impl TryFrom<String> for SpecAttributeKind {
             "after_expiry_if" => Ok(SpecAttributeKind::AfterExpiryIf),
             "pure" => Ok(SpecAttributeKind::Pure),
             "trusted" => Ok(SpecAttributeKind::Trusted),
+            "invariant" => Ok(SpecAttributeKind::Invariant),
+            "predicate" => Ok(SpecAttributeKind::Predicate),
+            "decreases" => Ok(SpecAttributeKind::Decreases),
             _ => Err(name),
         }
     }
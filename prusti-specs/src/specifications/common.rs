@@ -3,7 +3,12 @@
 //! Please see the `parser.rs` file for more information about
 //! specifications.
 
+use crate::source_info;
+use proc_macro2::Span;
+use quote::ToTokens;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt::{Display, Debug};
 use uuid::Uuid;
@@ -20,11 +25,15 @@ pub enum SpecType {
 }
 
 #[derive(Debug)]
-/// A conversion from string into specification type error.
+/// A conversion from string into specification type or refinement mode
+/// error.
 pub enum TryFromStringError {
     /// Reported when the string being converted is not one of the
     /// following: `requires`, `ensures`, `invariant`.
     UnknownSpecificationType,
+    /// Reported when the string being converted is not one of the
+    /// following: `overwrite`, `extend`.
+    UnknownRefinementMode,
 }
 
 impl<'a> TryFrom<&'a str> for SpecType {
@@ -40,6 +49,45 @@ impl<'a> TryFrom<&'a str> for SpecType {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How the specification of a trait implementation method is combined with
+/// the specification of the trait method.
+pub enum RefinementMode {
+    /// Any pre-/postcondition of the implementation replaces the one of the
+    /// trait.
+    Overwrite,
+    /// The preconditions are disjoined and the postconditions conjoined, so
+    /// that the implementation only states what it adds to the trait.
+    Extend,
+}
+
+impl RefinementMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefinementMode::Overwrite => "overwrite",
+            RefinementMode::Extend => "extend",
+        }
+    }
+}
+
+impl Default for RefinementMode {
+    fn default() -> Self {
+        RefinementMode::Overwrite
+    }
+}
+
+impl<'a> TryFrom<&'a str> for RefinementMode {
+    type Error = TryFromStringError;
+
+    fn try_from(mode: &str) -> Result<RefinementMode, TryFromStringError> {
+        match mode {
+            "overwrite" => Ok(RefinementMode::Overwrite),
+            "extend" => Ok(RefinementMode::Extend),
+            _ => Err(TryFromStringError::UnknownRefinementMode),
+        }
+    }
+}
+
 #[derive(
     Debug, Default, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, PartialOrd, Ord,
 )]
@@ -70,17 +118,83 @@ impl SpecificationId {
     }
 }
 
-pub(crate) struct SpecificationIdGenerator {}
+/// Generates specification IDs that are derived from a seed describing the
+/// specification (see `register_seed`), so that expanding the same
+/// specification again yields the same IDs.
+pub(crate) struct SpecificationIdGenerator {
+    seed: String,
+    count: u64,
+}
 
 impl SpecificationIdGenerator {
-    pub(crate) fn new() -> Self {
-        Self {}
+    pub(crate) fn new(seed: String) -> Self {
+        Self { seed, count: 0 }
     }
     pub(crate) fn generate(&mut self) -> SpecificationId {
-        SpecificationId(Uuid::new_v4())
+        let key = format!("{}|{}", self.seed, self.count);
+        self.count += 1;
+        SpecificationId(Uuid::from_u128(stable_hash(key.as_bytes())))
     }
 }
 
+thread_local! {
+    /// The seeds of the specifications of the crate being expanded, with the
+    /// number of specifications that were generated from each.
+    static SEEDS: RefCell<HashMap<String, u64>> = RefCell::new(HashMap::new());
+}
+
+/// Record the seed of a specification of an item, failing if a specification
+/// of the crate was generated from the same seed before. Identical items, for
+/// example from a `macro_rules!`, cannot be told apart otherwise, and would
+/// get the same IDs.
+///
+/// The seeds are only recorded inside the compiler, which expands the macros
+/// of a single crate; elsewhere, for example in tests, the same specification
+/// may be expanded repeatedly.
+pub(crate) fn register_seed(seed: &str, span: Span) -> syn::Result<()> {
+    if !source_info::is_enabled() {
+        return Ok(());
+    }
+    SEEDS.with(|seeds| {
+        let mut seeds = seeds.borrow_mut();
+        if seeds.contains_key(seed) {
+            return Err(syn::Error::new(
+                span,
+                "the specification cannot be told apart from an identical specification of \
+                 an identical item in the same file; rename one of the items",
+            ));
+        }
+        seeds.insert(seed.to_string(), 1);
+        Ok(())
+    })
+}
+
+/// Extend the seed of a specification in a function body, which has no item
+/// to tell it apart from identical specifications in the same file, by the
+/// number of such specifications expanded before it. Like `register_seed`,
+/// this only has an effect inside the compiler.
+pub(crate) fn number_seed(seed: String) -> String {
+    if !source_info::is_enabled() {
+        return seed;
+    }
+    SEEDS.with(|seeds| {
+        let mut seeds = seeds.borrow_mut();
+        let count = seeds.entry(seed.clone()).or_insert(0);
+        *count += 1;
+        format!("{}|{}", seed, *count - 1)
+    })
+}
+
+/// A 128-bit FNV-1a hash, which, unlike `std`'s hashers, is guaranteed to be
+/// the same across compiler versions and platforms.
+fn stable_hash(bytes: &[u8]) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u128::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
 #[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 /// A unique ID of the Rust expression used in the specification.
 pub struct ExpressionId(u64);
@@ -111,6 +225,9 @@ impl Into<u64> for ExpressionId {
     }
 }
 
+/// Generates the names of the helper items of external specifications. Like
+/// specification IDs, the names are derived from the specified item and the
+/// file that contains it, so that they are stable across builds.
 pub(crate) struct NameGenerator {}
 
 impl NameGenerator {
@@ -125,17 +242,46 @@ impl NameGenerator {
                 }
             }
             _ => {
-                return Err("expected a path".to_string());
+                return Err(format!(
+                    "external specifications of impl blocks are only supported for path types, found `{}`",
+                    item.self_ty.to_token_stream(),
+                ));
             }
         };
-        let uuid = Uuid::new_v4().to_simple();
+        if let Some((_, trait_path, _)) = &item.trait_ {
+            for seg in trait_path.segments.iter() {
+                path_str.push_str(&seg.ident.to_string());
+            }
+        }
+        let id = stable_hash(format!(
+            "impl|{}|{}|{}|{}",
+            source_info::crate_file(item.impl_token.span),
+            item.generics.to_token_stream(),
+            item.self_ty.to_token_stream(),
+            item.trait_.as_ref().map(|(_, path, _)| path.to_token_stream()).unwrap_or_default(),
+        ).as_bytes());
+
+        Ok(format!("PrustiStruct{}{:032x}", path_str, id))
+    }
 
-        Ok(format!("PrustiStruct{}{}", path_str, uuid))
+    pub(crate) fn generate_trait_struct_name(&self, item: &syn::ItemTrait) -> String {
+        let id = stable_hash(format!(
+            "trait|{}|{}|{}",
+            source_info::crate_file(item.ident.span()),
+            item.ident,
+            item.generics.to_token_stream(),
+        ).as_bytes());
+        format!("PrustiTrait{}{:032x}", item.ident, id)
     }
 
-    pub(crate) fn generate_mod_name(&self, ident: &syn::Ident) -> String {
-        let uuid = Uuid::new_v4().to_simple();
-        format!("{}{}", ident.to_string(), uuid)
+    pub(crate) fn generate_mod_name(&self, path: &syn::Path, ident: &syn::Ident) -> String {
+        let id = stable_hash(format!(
+            "mod|{}|{}|{}",
+            source_info::crate_file(ident.span()),
+            path.to_token_stream(),
+            ident,
+        ).as_bytes());
+        format!("{}{:032x}", ident.to_string(), id)
     }
 }
 
@@ -204,7 +350,7 @@ impl<EID, ET> IntoIterator for TriggerSet<EID, ET> {
 }
 
 #[derive(Debug, Clone)]
-/// A sequence of variables used in the forall.
+/// A sequence of variables used in the forall or exists.
 pub struct ForAllVars<EID, AT> {
     /// Identifier of the specification to which this sequence of variables
     /// belongs.
@@ -213,6 +359,9 @@ pub struct ForAllVars<EID, AT> {
     pub id: EID,
     /// Variables.
     pub vars: Vec<AT>,
+    /// The span of the quantifier (from the keyword to the closing
+    /// parenthesis) or of the bounds of the type condition.
+    pub span: proc_macro2::Span,
 }
 
 #[derive(Debug, Clone)]
@@ -222,9 +371,16 @@ pub enum AssertionKind<EID, ET, AT> {
     Expr(Expression<EID, ET>),
     /// Conjunction &&.
     And(Vec<Assertion<EID, ET, AT>>),
+    /// Disjunction ||.
+    Or(Vec<Assertion<EID, ET, AT>>),
+    /// Negation !.
+    Not(Assertion<EID, ET, AT>),
     /// Implication ==>
     Implies(Assertion<EID, ET, AT>, Assertion<EID, ET, AT>),
-    /// TODO < Even > ==> x % 2 == 0
+    /// Biconditional <==>
+    Iff(Assertion<EID, ET, AT>, Assertion<EID, ET, AT>),
+    /// Type condition <T: Trait> ==> A, which holds if the bounds are not
+    /// satisfied or if A holds
     TypeCond(ForAllVars<EID, AT>, Assertion<EID, ET, AT>),
     /// Quantifier
     ForAll(
@@ -232,6 +388,12 @@ pub enum AssertionKind<EID, ET, AT> {
         TriggerSet<EID, ET>,
         Assertion<EID, ET, AT>,
     ),
+    /// Existential quantifier
+    Exists(
+        ForAllVars<EID, AT>,
+        TriggerSet<EID, ET>,
+        Assertion<EID, ET, AT>,
+    ),
 }
 
 #[derive(Debug, Clone)]
@@ -246,6 +408,14 @@ pub struct Pledge<EID, ET, AT> {
     pub rhs: Assertion<EID, ET, AT>,
 }
 
+#[derive(Debug, Clone)]
+/// Termination measure `decreases(e1, e2, ...)` of a function or a loop. The
+/// expressions are compared lexicographically.
+pub struct Variant<EID, ET> {
+    /// The components of the measure, the most significant one first.
+    pub exprs: Vec<Expression<EID, ET>>,
+}
+
 #[derive(Debug, Clone)]
 /// Specification such as precondition, postcondition, or invariant.
 pub struct Specification<EID, ET, AT> {
@@ -328,9 +498,11 @@ impl<EID: Clone + Debug, ET: Clone + Debug, AT: Clone + Debug> SpecificationSet<
     /// https://ethz.ch/content/dam/ethz/special-interest/infk/chair-program-method/pm/documents/Education/Theses/Matthias_Erdin_MA_report.pdf
     /// pp 19-23
     ///
-    /// In other words, any pre-/post-condition provided by `other` will overwrite any provided by
-    /// `self`.
-    pub fn refine(&self, other: &Self) -> Self {
+    /// In other words, with `RefinementMode::Overwrite` any pre-/post-condition provided by
+    /// `other` will overwrite any provided by `self`. With `RefinementMode::Extend`, the
+    /// precondition of `other` is disjoined with the one of `self`, and the postcondition and
+    /// pledges of `other` are conjoined with the ones of `self`.
+    pub fn refine(&self, other: &Self, mode: RefinementMode) -> Self {
         let mut pres = vec![];
         let mut posts = vec![];
         let mut pledges = vec![];
@@ -348,22 +520,101 @@ impl<EID: Clone + Debug, ET: Clone + Debug, AT: Clone + Debug> SpecificationSet<
                 unreachable!("Unexpected: {:?}", self)
             }
         };
-        if ref_pre.is_empty() {
-            pres.append(&mut base_pre.clone());
-        } else {
-            pres.append(&mut ref_pre.clone());
+        match mode {
+            RefinementMode::Overwrite => {
+                if ref_pre.is_empty() {
+                    pres.append(&mut base_pre.clone());
+                } else {
+                    pres.append(&mut ref_pre.clone());
+                }
+                if ref_post.is_empty() {
+                    posts.append(&mut base_post.clone());
+                } else {
+                    posts.append(&mut ref_post.clone());
+                }
+                if ref_pledges.is_empty() {
+                    pledges.append(&mut base_pledges.clone());
+                } else {
+                    pledges.append(&mut ref_pledges.clone());
+                }
+            }
+            RefinementMode::Extend => {
+                // an empty precondition of the trait is `true`, which stays `true` when
+                // disjoined with the precondition of the implementation
+                if ref_pre.is_empty() || base_pre.is_empty() {
+                    pres.append(&mut base_pre.clone());
+                } else {
+                    pres.push(Assertion {
+                        kind: box AssertionKind::Or(vec![
+                            Assertion { kind: box AssertionKind::And(base_pre.clone()) },
+                            Assertion { kind: box AssertionKind::And(ref_pre.clone()) },
+                        ]),
+                    });
+                }
+                posts.append(&mut base_post.clone());
+                posts.append(&mut ref_post.clone());
+                pledges.append(&mut base_pledges.clone());
+                pledges.append(&mut ref_pledges.clone());
+            }
+        }
+        SpecificationSet::Procedure(ProcedureSpecification { pres, posts, pledges })
+    }
+
+    /// The obligations for `other`, the specification of a trait
+    /// implementation method, to be a behavioral subtype of `self`, the
+    /// specification of the trait method: the trait's precondition must
+    /// imply the implementation's precondition and the implementation's
+    /// postcondition must imply the trait's postcondition. Only the parts of
+    /// the specification that `other` overwrites (see `refine`) lead to
+    /// obligations; a specification refined with `RefinementMode::Extend` is
+    /// a behavioral subtype by construction.
+    pub fn subtyping_obligations(
+        &self,
+        other: &Self,
+        mode: RefinementMode,
+    ) -> Vec<SubtypingObligation<EID, ET, AT>> {
+        let (trait_spec, impl_spec) = match (self, other) {
+            (SpecificationSet::Procedure(trait_spec), SpecificationSet::Procedure(impl_spec)) => {
+                (trait_spec, impl_spec)
+            }
+            _ => unreachable!("Unexpected: {:?}, {:?}", self, other),
+        };
+        let mut obligations = vec![];
+        if mode == RefinementMode::Extend {
+            return obligations;
         }
-        if ref_post.is_empty() {
-            posts.append(&mut base_post.clone());
-        } else {
-            posts.append(&mut ref_post.clone());
+        if !impl_spec.pres.is_empty() {
+            obligations.push(SubtypingObligation {
+                kind: SubtypingObligationKind::Precondition,
+                lhs: trait_spec.pres.clone(),
+                rhs: impl_spec.pres.clone(),
+            });
         }
-        if ref_pledges.is_empty() {
-            pledges.append(&mut base_pledges.clone());
-        } else {
-            pledges.append(&mut ref_pledges.clone());
+        if !impl_spec.posts.is_empty() {
+            obligations.push(SubtypingObligation {
+                kind: SubtypingObligationKind::Postcondition,
+                lhs: impl_spec.posts.clone(),
+                rhs: trait_spec.posts.clone(),
+            });
         }
-        SpecificationSet::Procedure(ProcedureSpecification { pres, posts, pledges })
+        obligations
     }
+}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The part of a specification that a behavioral subtyping obligation is
+/// about.
+pub enum SubtypingObligationKind {
+    Precondition,
+    Postcondition,
+}
+
+#[derive(Debug, Clone)]
+/// An obligation `lhs ==> rhs`, where both sides are conjunctions, that a
+/// trait implementation method has to satisfy to be a behavioral subtype of
+/// the trait method.
+pub struct SubtypingObligation<EID, ET, AT> {
+    pub kind: SubtypingObligationKind,
+    pub lhs: Vec<Assertion<EID, ET, AT>>,
+    pub rhs: Vec<Assertion<EID, ET, AT>>,
 }
\ No newline at end of file
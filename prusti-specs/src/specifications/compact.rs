@@ -0,0 +1,193 @@
+//! A compact text encoding of JSON values, used for the specifications in
+//! the expanded code when `PRUSTI_SPEC_ENCODING=compact` is set.
+//!
+//! Serialized specifications repeat the same strings many times (object keys
+//! and the specification ID of every expression), and JSON quotes every
+//! string, which have to be escaped in the attributes that carry the
+//! specifications. Therefore, all strings are stored once in a table at the
+//! start and referenced by their index:
+//!
+//! ```text
+//! encoding := MAGIC count ';' (length ':' bytes)* value
+//! value    := 'n' | 't' | 'f'                   null, true, false
+//!           | 'u' digits ';' | 'i' '-' digits ';' | 'd' float ';'
+//!           | 's' index ';'                     string
+//!           | '[' value* ']'                    array
+//!           | '{' (index ';' value)* '}'        object
+//! ```
+//!
+//! where `length` is the length of a string in bytes.
+
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+
+/// The prefix of the compact encoding, which distinguishes it from JSON.
+pub(crate) const MAGIC: &str = "pc1:";
+
+pub(crate) fn encode(value: &Value) -> String {
+    let mut encoder = Encoder { strings: vec![], indices: HashMap::new(), output: String::new() };
+    encoder.encode_value(value);
+    let mut encoded = format!("{}{};", MAGIC, encoder.strings.len());
+    for string in &encoder.strings {
+        encoded.push_str(&format!("{}:{}", string.len(), string));
+    }
+    encoded.push_str(&encoder.output);
+    encoded
+}
+
+struct Encoder<'a> {
+    strings: Vec<&'a str>,
+    indices: HashMap<&'a str, usize>,
+    output: String,
+}
+
+impl<'a> Encoder<'a> {
+    fn encode_string(&mut self, string: &'a str) {
+        let strings = &mut self.strings;
+        let index = *self.indices.entry(string).or_insert_with(|| {
+            strings.push(string);
+            strings.len() - 1
+        });
+        self.output.push_str(&format!("{};", index));
+    }
+
+    fn encode_value(&mut self, value: &'a Value) {
+        match value {
+            Value::Null => self.output.push('n'),
+            Value::Bool(true) => self.output.push('t'),
+            Value::Bool(false) => self.output.push('f'),
+            Value::Number(number) => {
+                if let Some(number) = number.as_u64() {
+                    self.output.push_str(&format!("u{};", number));
+                } else if let Some(number) = number.as_i64() {
+                    self.output.push_str(&format!("i{};", number));
+                } else {
+                    self.output.push_str(&format!("d{};", number));
+                }
+            }
+            Value::String(string) => {
+                self.output.push('s');
+                self.encode_string(string);
+            }
+            Value::Array(values) => {
+                self.output.push('[');
+                for value in values {
+                    self.encode_value(value);
+                }
+                self.output.push(']');
+            }
+            Value::Object(map) => {
+                self.output.push('{');
+                for (key, value) in map {
+                    self.encode_string(key);
+                    self.encode_value(value);
+                }
+                self.output.push('}');
+            }
+        }
+    }
+}
+
+/// Decode the compact encoding, without the `MAGIC` prefix.
+pub(crate) fn decode(input: &str) -> Result<Value, String> {
+    let mut decoder = Decoder { input, position: 0, strings: vec![] };
+    let count = decoder.number(';')?;
+    for _ in 0..count {
+        let length = decoder.number(':')?;
+        let string = decoder.input.get(decoder.position..decoder.position + length)
+            .ok_or_else(|| decoder.error("invalid string length"))?;
+        decoder.strings.push(string.to_string());
+        decoder.position += length;
+    }
+    let value = decoder.value()?;
+    if decoder.position != input.len() {
+        return Err(decoder.error("unexpected trailing input"));
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    input: &'a str,
+    position: usize,
+    strings: Vec<String>,
+}
+
+impl<'a> Decoder<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {} of the compact encoding", message, self.position)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let next = self.peek().ok_or_else(|| self.error("unexpected end of input"))?;
+        self.position += next.len_utf8();
+        Ok(next)
+    }
+
+    /// Read the text until `terminator` and consume the terminator.
+    fn text_until(&mut self, terminator: char) -> Result<&'a str, String> {
+        let rest = &self.input[self.position..];
+        let end = rest.find(terminator).ok_or_else(|| self.error("unexpected end of input"))?;
+        self.position += end + terminator.len_utf8();
+        Ok(&rest[..end])
+    }
+
+    fn number(&mut self, terminator: char) -> Result<usize, String> {
+        let text = self.text_until(terminator)?;
+        text.parse().map_err(|_| self.error(&format!("invalid number `{}`", text)))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let index = self.number(';')?;
+        match self.strings.get(index) {
+            Some(string) => Ok(string.clone()),
+            None => Err(self.error(&format!("invalid string index {}", index))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        Ok(match self.next()? {
+            'n' => Value::Null,
+            't' => Value::Bool(true),
+            'f' => Value::Bool(false),
+            'u' => {
+                let text = self.text_until(';')?;
+                let number: u64 = text.parse().map_err(|_| self.error("invalid number"))?;
+                Value::Number(number.into())
+            }
+            'i' => {
+                let text = self.text_until(';')?;
+                let number: i64 = text.parse().map_err(|_| self.error("invalid number"))?;
+                Value::Number(number.into())
+            }
+            'd' => {
+                let text = self.text_until(';')?;
+                let number = text.parse().ok().and_then(Number::from_f64)
+                    .ok_or_else(|| self.error("invalid number"))?;
+                Value::Number(number)
+            }
+            's' => Value::String(self.string()?),
+            '[' => {
+                let mut values = vec![];
+                while self.peek() != Some(']') {
+                    values.push(self.value()?);
+                }
+                self.position += 1;
+                Value::Array(values)
+            }
+            '{' => {
+                let mut map = Map::new();
+                while self.peek() != Some('}') {
+                    let key = self.string()?;
+                    map.insert(key, self.value()?);
+                }
+                self.position += 1;
+                Value::Object(map)
+            }
+            other => return Err(self.error(&format!("unexpected `{}`", other))),
+        })
+    }
+}
@@ -1,18 +1,161 @@
 use super::untyped;
-use serde::{Deserialize, Serialize};
+use proc_macro2::Span;
+use quote::ToTokens;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use super::common;
+use super::compact;
+use crate::source_info;
+use std::fmt;
+use syn::spanned::Spanned;
+
+/// The version of the serialization format. It must be increased whenever
+/// the format changes, so that a driver that was built against a different
+/// version of this crate than the macros reports a clear error instead of
+/// misinterpreting the specifications.
+pub const FORMAT_VERSION: u64 = 3;
+
+/// The encoding of serialized specifications. The decoder detects the
+/// encoding automatically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Plain JSON.
+    Json,
+    /// The compact encoding of `compact.rs`.
+    Compact,
+}
+
+impl Encoding {
+    /// The encoding selected by the `PRUSTI_SPEC_ENCODING` environment
+    /// variable (`json` or `compact`), JSON by default. An invalid value is
+    /// reported at the macro invocation being expanded.
+    ///
+    /// The build script of `prusti-contracts-internal` tells Cargo to rebuild
+    /// the macros when the variable changes, so that crates using them are
+    /// expanded again.
+    pub fn configured() -> syn::Result<Self> {
+        match std::env::var("PRUSTI_SPEC_ENCODING") {
+            Ok(encoding) => match encoding.trim().to_lowercase().as_str() {
+                "json" | "" => Ok(Encoding::Json),
+                "compact" => Ok(Encoding::Compact),
+                _ => Err(syn::Error::new(
+                    Span::call_site(),
+                    format!(
+                        "invalid value `{}` of PRUSTI_SPEC_ENCODING, expected `json` or `compact`",
+                        encoding,
+                    ),
+                )),
+            },
+            Err(_) => Ok(Encoding::Json),
+        }
+    }
+}
+
+/// The envelope in which specifications are serialized.
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u64,
+    spec: &'a T,
+}
+
+/// An error while decoding a serialized specification.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The input is not valid JSON or does not match the format.
+    Malformed(serde_json::Error),
+    /// The input has no format version, i.e. it was produced by a version
+    /// of Prusti from before the format was versioned.
+    MissingVersion,
+    /// The input was produced with a different version of the format.
+    UnsupportedVersion { found: u64 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed(err) => write!(f, "malformed specification: {}", err),
+            DecodeError::MissingVersion => write!(
+                f,
+                "the specification has no format version (expected version {}); \
+                 prusti-contracts is older than the Prusti driver",
+                FORMAT_VERSION,
+            ),
+            DecodeError::UnsupportedVersion { found } => write!(
+                f,
+                "the specification has format version {}, but version {} is expected; \
+                 prusti-contracts and the Prusti driver are built from different versions",
+                found,
+                FORMAT_VERSION,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Malformed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn encode<T: Serialize>(spec: &T, encoding: Encoding) -> String {
+    // the serialized types contain no maps and no custom serializers, so
+    // serialization cannot fail
+    let envelope = Envelope { version: FORMAT_VERSION, spec };
+    match encoding {
+        Encoding::Json => serde_json::to_string(&envelope).unwrap(),
+        Encoding::Compact => compact::encode(&serde_json::to_value(&envelope).unwrap()),
+    }
+}
+
+fn decode<T: DeserializeOwned>(input: &str) -> Result<T, DecodeError> {
+    let mut value: serde_json::Value = if let Some(encoded) = input.strip_prefix(compact::MAGIC) {
+        compact::decode(encoded)
+            .map_err(|msg| DecodeError::Malformed(serde::de::Error::custom(msg)))?
+    } else {
+        serde_json::from_str(input).map_err(DecodeError::Malformed)?
+    };
+    // the version is checked before the specification, so that a different
+    // version is reported as such even if the specification does not parse
+    match value.get("version") {
+        None => return Err(DecodeError::MissingVersion),
+        Some(version) => match version.as_u64() {
+            Some(FORMAT_VERSION) => {}
+            Some(found) => return Err(DecodeError::UnsupportedVersion { found }),
+            None => return Err(DecodeError::Malformed(serde::de::Error::custom(
+                format!("invalid format version `{}`", version)
+            ))),
+        },
+    }
+    let spec = value.get_mut("spec").map(serde_json::Value::take).unwrap_or_default();
+    serde_json::from_value(spec).map_err(DecodeError::Malformed)
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Assertion {
     pub kind: Box<AssertionKind>,
+    /// The location of the assertion. Not recorded for `Expr` assertions,
+    /// whose expression already has a location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
+    /// The message of the clause, as in `requires(x > 0, "x is positive")`.
+    /// Only recorded for the assertion of a whole clause.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum AssertionKind {
     Expr(Expression),
     And(Vec<Assertion>),
+    Or(Vec<Assertion>),
+    Not(Assertion),
     Implies(Assertion, Assertion),
+    Iff(Assertion, Assertion),
+    TypeCond(TypeCondBounds, Assertion),
     ForAll(ForAllVars, Assertion, TriggerSet),
+    Exists(ForAllVars, Assertion, TriggerSet),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,6 +164,47 @@ pub struct Expression {
     pub spec_id: untyped::SpecificationId,
     /// Identifier of the expression within the specification.
     pub expr_id: untyped::ExpressionId,
+    /// The location of the expression.
+    pub location: SourceLocation,
+}
+
+/// A position in a source file.
+#[derive(Serialize, Deserialize)]
+pub struct LineColumn {
+    /// 1-indexed line.
+    pub line: usize,
+    /// 0-indexed column (in UTF-8 characters).
+    pub column: usize,
+}
+
+/// The location of a fragment of a specification in the source code.
+#[derive(Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// The file; only known when running inside the compiler.
+    pub file: Option<String>,
+    pub start: LineColumn,
+    pub end: LineColumn,
+    /// The original source text of the fragment; only known when running
+    /// inside the compiler.
+    pub source: Option<String>,
+}
+
+impl SourceLocation {
+    fn from_span(span: Span) -> Self {
+        let start = span.start();
+        let end = span.end();
+        SourceLocation {
+            file: source_info::file(span),
+            start: LineColumn { line: start.line, column: start.column },
+            end: LineColumn { line: end.line, column: end.column },
+            source: source_info::source_text(span),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Variant {
+    pub exprs: Vec<Expression>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -30,6 +214,17 @@ pub struct ForAllVars {
     pub count: usize,
 }
 
+/// The bounds of a type condition. The body of the type condition is
+/// type-checked in the spec item whose `prusti::type_cond` attribute is
+/// `<spec_id>_<expr_id>`.
+#[derive(Serialize, Deserialize)]
+pub struct TypeCondBounds {
+    pub spec_id: untyped::SpecificationId,
+    pub expr_id: untyped::ExpressionId,
+    /// The bounds as written, for example `T: Copy`.
+    pub bounds: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TriggerSet(pub Vec<Trigger>);
 
@@ -38,9 +233,14 @@ pub struct Trigger(pub Vec<Expression>);
 
 impl untyped::Expression {
     fn to_structure(&self) -> Expression {
+        let mut location = SourceLocation::from_span(self.expr.span());
+        if location.source.is_none() {
+            location.source = Some(self.expr.to_token_stream().to_string());
+        }
         Expression {
             spec_id: self.spec_id.clone(),
             expr_id: self.id.clone(),
+            location,
         }
     }
 }
@@ -53,6 +253,14 @@ impl common::ForAllVars<untyped::ExpressionId, untyped::Arg> {
             expr_id: self.id.clone(),
         }
     }
+
+    fn to_type_cond_bounds(&self) -> TypeCondBounds {
+        TypeCondBounds {
+            spec_id: self.spec_id,
+            expr_id: self.id,
+            bounds: self.vars.iter().map(|var| var.to_token_stream().to_string()).collect(),
+        }
+    }
 }
 
 impl untyped::TriggerSet {
@@ -84,40 +292,98 @@ impl untyped::AssertionKind {
             And(assertions) => {
                 AssertionKind::And(
                     assertions.into_iter()
-                              .map(|assertion| Assertion { kind: box assertion.kind.to_structure() })
+                              .map(|assertion| assertion.to_structure())
+                              .collect()
+                )
+            }
+            Or(assertions) => {
+                AssertionKind::Or(
+                    assertions.iter()
+                              .map(|assertion| assertion.to_structure())
                               .collect()
                 )
             }
+            Not(assertion) => AssertionKind::Not(assertion.to_structure()),
             Implies(lhs, rhs) => AssertionKind::Implies(
                 lhs.to_structure(),
                 rhs.to_structure()
             ),
+            Iff(lhs, rhs) => AssertionKind::Iff(
+                lhs.to_structure(),
+                rhs.to_structure()
+            ),
             ForAll(vars, triggers, body) => AssertionKind::ForAll(
                 vars.to_structure(),
                 body.to_structure(),
                 triggers.to_structure(),
             ),
-            x => {
-                unimplemented!("{:?}", x);
-            }
+            Exists(vars, triggers, body) => AssertionKind::Exists(
+                vars.to_structure(),
+                body.to_structure(),
+                triggers.to_structure(),
+            ),
+            TypeCond(vars, body) => AssertionKind::TypeCond(
+                vars.to_type_cond_bounds(),
+                body.to_structure(),
+            ),
         }
     }
 }
 
 impl untyped::Assertion {
     fn to_structure(&self) -> Assertion {
+        let location = match &*self.kind {
+            common::AssertionKind::Expr(_) => None,
+            _ => Some(SourceLocation::from_span(self.span())),
+        };
         Assertion {
             kind: box self.kind.to_structure(),
+            location,
+            message: None,
         }
     }
 }
 
-pub fn to_json_string(assertion: &untyped::Assertion) -> String {
-    serde_json::to_string(&assertion.to_structure()).unwrap()
+/// Serialize the assertion of a clause with the given message in the
+/// encoding selected by `PRUSTI_SPEC_ENCODING`.
+pub fn to_json_string(assertion: &untyped::Assertion, message: Option<&str>) -> syn::Result<String> {
+    let mut assertion = assertion.to_structure();
+    assertion.message = message.map(str::to_string);
+    Ok(assertion.encode(Encoding::configured()?))
 }
 
 impl Assertion {
-    pub fn from_json_string(json: &str) -> Self {
-        serde_json::from_str(&json).unwrap()
+    /// Decode an assertion in any of the encodings.
+    pub fn from_json_string(json: &str) -> Result<Self, DecodeError> {
+        decode(json)
+    }
+
+    pub fn encode(&self, encoding: Encoding) -> String {
+        encode(self, encoding)
+    }
+}
+
+impl untyped::Variant {
+    fn to_structure(&self) -> Variant {
+        Variant {
+            exprs: self.exprs.iter().map(|expr| expr.to_structure()).collect(),
+        }
+    }
+}
+
+/// Serialize the termination measure in the encoding selected by
+/// `PRUSTI_SPEC_ENCODING`.
+pub fn variant_to_json_string(variant: &untyped::Variant) -> syn::Result<String> {
+    Ok(variant.to_structure().encode(Encoding::configured()?))
+}
+
+impl Variant {
+    /// Decode a termination measure in any of the encodings.
+    pub fn from_json_string(json: &str) -> Result<Self, DecodeError> {
+        decode(json)
+    }
+
+    pub fn encode(&self, encoding: Encoding) -> String {
+        encode(self, encoding)
     }
 }
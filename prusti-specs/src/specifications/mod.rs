@@ -1,10 +1,20 @@
-/// Each atomic Prusti assertion (`A`) is a Rust expression (`E`)
-/// or a `forall` expression. Atomic Prusti assertions can be joined together
-/// using the following two operators, forming Prusti assertions:
+/// Each atomic Prusti assertion (`A`) is a Rust expression (`E`),
+/// a `forall` expression, or an `exists` expression. Atomic Prusti assertions
+/// can be joined together using the following operators, forming Prusti
+/// assertions:
+/// - `!A` (negation)
 /// - `A && A` (conjunction)
+/// - `A || A` (disjunction)
 /// - `A ==> A` (implication)
+/// - `A <==> A` (biconditional)
 ///
-/// `==>` has weaker binding than `&&` and is right-associative.
+/// `&&` and `||` cannot be mixed without parentheses, since it is easy to
+/// get their binding wrong: `E && E || E` is rejected, while
+/// `(E && E) || E` is accepted. `==>` has weaker binding than both of them
+/// and is right-associative. For example, `(E && E) || E ==> E` is parsed as
+/// `((E && E) || E) ==> E`. `<==>` has the
+/// weakest binding and is also right-associative, so `A ==> A <==> A` is
+/// parsed as `(A ==> A) <==> A`.
 ///
 /// Parentheses can be used as usual, i.e. `(A ==> A) && A`
 /// is a Prusti assertion.
@@ -14,22 +24,20 @@
 /// `forall(|NAME1: TYPE1, NAME2: TYPE2, ...| A)`
 /// `forall(|NAME1: TYPE1, NAME2: TYPE2, ...| A, triggers=[(E, ...), ...])`
 ///
-/// Prusti assertions can only be joined together by `&&` and `==>`, for example
-/// the following is not allowed, since `(E ==> E)` is a Prusti assertion:
-/// `(E ==> E) || E`
+/// `exists` expression uses the same syntax as `forall`:
+/// `exists(|NAME1: TYPE1, NAME2: TYPE2, ...| A)`
+/// `exists(|NAME1: TYPE1, NAME2: TYPE2, ...| A, triggers=[(E, ...), ...])`
 ///
-/// However, this is fine, as `&&` is also a Rust operator, and this whole thing
-/// is interpreted as a Rust expression:
-/// `(E && E) || E`
+/// A type condition restricts an assertion to the instantiations of generic
+/// parameters that satisfy the given bounds:
+/// `<NAME1: BOUND1, NAME2: BOUND2 + BOUND3, ...> ==> A`
+/// The type condition extends as far as the rhs of an implication would, for
+/// example `<T: Copy> ==> A && A` is parsed as `<T: Copy> ==> (A && A)`.
 ///
-/// Having `&&` and `||` in the same subexpression is not allowed to prevent
-/// ambiguities. Those are all illegal:
-/// `E && E || E`
-/// `A && (E && E || E)`
-///
-/// However, this is fine, since the implication resolves the ambiguity:
-/// (note that both the lhs and rhs of the implication form a Prusti assertion)
-/// `E && E ==> E || E`
+/// `!` is treated as a Prusti negation only if it is applied to a quantifier
+/// or to a parenthesized block that is followed by an operator (or by nothing
+/// at all), for example `!forall(...)` or `!(A ==> A) && A`. Otherwise, it is
+/// a part of a Rust expression, for example `!v.is_empty()`.
 ///
 /// Basic parser usage (`tokens` is of type `proc_macro2::TokenStream`):
 /// ```ignore
@@ -38,6 +46,7 @@
 /// ```
 
 pub mod common;
+mod compact;
 pub mod json;
 pub mod untyped;
 pub mod preparser;
@@ -2,7 +2,7 @@
 /// parses the resulting Rust expressions, and then assembles the composite
 /// Prusti assertion.
 
-use proc_macro2::{Delimiter, Group, Span, TokenStream, TokenTree};
+use proc_macro2::{Delimiter, Group, Spacing, Span, TokenStream, TokenTree};
 use std::collections::VecDeque;
 use std::mem;
 use syn::parse::{ParseStream, Parse};
@@ -15,6 +15,8 @@ use syn::spanned::Spanned;
 pub type AssertionWithoutId = common::Assertion<(), syn::Expr, Arg>;
 pub type PledgeWithoutId = common::Pledge<(), syn::Expr, Arg>;
 pub type ExpressionWithoutId = common::Expression<(), syn::Expr>;
+/// The bound variables, triggers and body of a quantifier.
+type QuantifierWithoutId = (ForAllVars<(), Arg>, TriggerSet<(), syn::Expr>, AssertionWithoutId);
 
 /// A helper to operate the stream of tokens.
 #[derive(Debug, Clone)]
@@ -45,26 +47,22 @@ impl ParserStream {
     /// potentially ambiguous subexpressions.
     fn contains_both_and_or(&mut self) -> bool {
         let mut stream = self.clone();
-        let mut contains_and = false;
-        let mut contains_or = false;
         let mut and_span: Option<Span> = None;
         let mut or_span: Option<Span> = None;
 
         while !stream.is_empty() {
             // subexpression contains and
             if stream.peek_operator("&&") {
-                contains_and = true;
                 and_span = Some(stream.tokens.front().span());
             }
             // subexpression contains or
             else if stream.peek_operator("||") {
-                contains_or = true;
                 or_span = Some(stream.tokens.front().span());
             }
             // implies met - reset subexpression
             else if stream.peek_operator("==>") {
-                contains_and = false;
-                contains_or = false;
+                and_span = None;
+                or_span = None;
             }
             // nested expression met - resolve it recursively
             else if stream.peek_parenthesized_block() {
@@ -74,19 +72,16 @@ impl ParserStream {
                     self.span = nested_stream.span;
                     return true;
                 }
+                continue;
             }
             // if a subexpression contains both `&&` and `||`, construct the span and return
-            if contains_and && contains_or {
-                if let Some(a_s) = and_span {
-                    if let Some(o_s) = or_span {
-                        self.span = a_s.join(o_s).unwrap();
-                    }
-                }
+            if let (Some(and_span), Some(or_span)) = (and_span, or_span) {
+                self.span = and_span.join(or_span).unwrap_or(and_span);
                 return true;
             }
             stream.pop();
         }
-        return false;
+        false
     }
     /// Check if the token queue is empty.
     fn is_empty(&self) -> bool {
@@ -112,10 +107,32 @@ impl ParserStream {
         }
         false
     }
+    /// Check if the input starts with the quantifier keyword followed by a
+    /// parenthesized block or by `|`, so that variables of the same name are
+    /// not mistaken for the quantifier, while a malformed quantifier is still
+    /// reported as such. Must only be called at the start of an expression,
+    /// so that methods of the same name are not mistaken for the quantifier
+    /// either. Does not set the span.
+    fn peek_quantifier(&self, keyword: &str) -> bool {
+        match (self.tokens.get(0), self.tokens.get(1)) {
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(group))) if ident == keyword => {
+                group.delimiter() == Delimiter::Parenthesis
+            }
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(punct))) if ident == keyword => {
+                punct.as_char() == '|' && punct.spacing() == Spacing::Alone
+            }
+            _ => false,
+        }
+    }
     /// Check if the input starts with the operator. Does not set the span.
     fn peek_operator(&self, operator: &str) -> bool {
+        self.peek_operator_at(0, operator)
+    }
+    /// Check if the operator starts at the given position of the input.
+    /// Does not set the span.
+    fn peek_operator_at(&self, index: usize, operator: &str) -> bool {
         for (i, c) in operator.char_indices() {
-            if let Some(TokenTree::Punct(punct)) = self.tokens.get(i) {
+            if let Some(TokenTree::Punct(punct)) = self.tokens.get(index + i) {
                 if punct.as_char() != c {
                     return false;
                 }
@@ -136,7 +153,90 @@ impl ParserStream {
     }
     /// Check whether the input starts with an operator. Does not set the span.
     fn peek_any_operator(&self) -> bool {
-        self.peek_operator("==>") || self.peek_operator("&&")
+        self.peek_any_operator_at(0)
+    }
+    /// Check whether an operator starts at the given position of the input.
+    /// Does not set the span.
+    fn peek_any_operator_at(&self, index: usize) -> bool {
+        self.peek_operator_at(index, "<==>")
+            || self.peek_operator_at(index, "==>")
+            || self.peek_operator_at(index, "&&")
+            || self.peek_operator_at(index, "||")
+    }
+    /// Check if the input starts with a negated Prusti assertion, that is `!`
+    /// followed by a quantifier or by a parenthesized block, which in turn is
+    /// followed by an operator or the end of the input. Does not set the span.
+    fn peek_negated_assertion(&self) -> bool {
+        self.negated_assertion_len(0).is_some()
+    }
+    /// If a negated Prusti assertion starts at the given position of the
+    /// input, return the number of tokens it consists of.
+    fn negated_assertion_len(&self, index: usize) -> Option<usize> {
+        match self.tokens.get(index) {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '!' => {}
+            _ => return None,
+        }
+        let operand_len = match self.tokens.get(index + 1) {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '!' => {
+                // the operand is itself negated, its length is already checked
+                return self.negated_assertion_len(index + 1).map(|len| len + 1);
+            }
+            Some(TokenTree::Ident(ident))
+                if ident == "forall" || ident == "exists" => {
+                match self.tokens.get(index + 2) {
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => 2,
+                    _ => return None,
+                }
+            }
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => 1,
+            _ => return None,
+        };
+        let end = index + 1 + operand_len;
+        if end == self.tokens.len()
+            || self.peek_any_operator_at(end)
+            || self.peek_operator_at(end, ",") {
+            Some(1 + operand_len)
+        } else {
+            None
+        }
+    }
+    /// Check if the input starts with a type condition, that is a list of
+    /// bounds in angle brackets followed by `==>`. Does not set the span.
+    fn peek_type_condition(&self) -> bool {
+        self.type_condition_len().is_some()
+    }
+    /// If the input starts with a type condition, return the number of tokens
+    /// in between the angle brackets.
+    fn type_condition_len(&self) -> Option<usize> {
+        match self.tokens.front() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {}
+            _ => return None,
+        }
+        let mut depth = 0;
+        let mut after_dash = false;
+        for (i, token) in self.tokens.iter().enumerate().skip(1) {
+            if let TokenTree::Punct(punct) = token {
+                match punct.as_char() {
+                    '<' => depth += 1,
+                    // the `>` of `->` in bounds such as `Fn(i32) -> i32`
+                    '>' if after_dash => {}
+                    '>' if depth == 0 => {
+                        return if i > 1 && self.peek_operator_at(i + 1, "==>") {
+                            Some(i - 1)
+                        } else {
+                            None
+                        };
+                    }
+                    '>' => depth -= 1,
+                    _ => {}
+                }
+            }
+            after_dash = match token {
+                TokenTree::Punct(punct) => punct.as_char() == '-',
+                _ => false,
+            };
+        }
+        None
     }
     /// Check if the input starts with the operator and if yes, consume it
     /// and set the span to it.
@@ -158,6 +258,17 @@ impl ParserStream {
         true
     }
     /// Check if the input starts with a parenthesized block and if yes,
+    /// set the span to it.
+    fn peek_parenthesized_block(&mut self) -> bool {
+        match self.tokens.front() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                self.span = group.span();
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Check if the input starts with a parenthesized block and if yes,
     /// consume it and set the span to it.
     fn check_and_consume_parenthesized_block(&mut self) -> Option<Group> {
         if let Some(TokenTree::Group(group)) = self.tokens.front() {
@@ -172,20 +283,6 @@ impl ParserStream {
         }
         None
     }
-    /// Check if the input starts with a parenthesized block and if yes,
-    /// set the span to it.
-    fn peek_parenthesized_block(&mut self) -> bool {
-        if let Some(TokenTree::Group(group)) = self.tokens.front() {
-            if group.delimiter() == Delimiter::Parenthesis {
-                self.span = group.span();
-                return true;
-            }
-            else {
-                return false;
-            }
-        }
-        return false;
-    }
     /// Check if the input contains an operator
     /// (including parenthesized subexpressions) and if yes,
     /// set the span to the first occurrence of it.
@@ -196,7 +293,7 @@ impl ParserStream {
                 self.span = stream.span;
                 return true;
             }
-            if let Some(TokenTree::Group(group)) = self.tokens.front() {
+            if let Some(TokenTree::Group(group)) = stream.tokens.front() {
                 let mut nested_stream = ParserStream::from_token_stream(group.stream());
                 if nested_stream.contains_operator(operator) {
                     self.span = nested_stream.span;
@@ -219,6 +316,23 @@ impl ParserStream {
         stream.extend(t.into_iter());
         stream
     }
+    /// If the input ends with a comma followed by a string literal, remove
+    /// both and return the literal.
+    fn pop_message(&mut self) -> Option<syn::LitStr> {
+        let len = self.tokens.len();
+        match self.tokens.get(len.wrapping_sub(2)) {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == ',' => {}
+            _ => return None,
+        }
+        let message = match self.tokens.back() {
+            Some(TokenTree::Literal(literal)) => {
+                syn::parse2(TokenTree::Literal(literal.clone()).into()).ok()?
+            }
+            _ => return None,
+        };
+        self.tokens.truncate(len - 2);
+        Some(message)
+    }
     /// Convert the content into TokenStream.
     fn create_stream(&mut self) -> TokenStream {
         let mut stream = TokenStream::new();
@@ -231,7 +345,8 @@ impl ParserStream {
     }
 }
 
-/// The representation of an argument to `forall` (for example `a: i32`)
+/// The representation of an argument to `forall` (for example `a: i32`) or
+/// of a bound in a type condition (for example `T: Copy`)
 #[derive(Debug, Clone)]
 pub struct Arg {
     pub name: syn::Ident,
@@ -278,6 +393,9 @@ impl Parse for ForAllArgs {
 pub struct Parser {
     /// The helper to manipulate input.
     input: ParserStream,
+    /// Members of the disjunction that are already parsed. Each of them is
+    /// a conjunction.
+    disjuncts: Vec<AssertionWithoutId>,
     /// Members of the conjunction.
     conjuncts: Vec<AssertionWithoutId>,
     /// Currently being parsed Rust expression.
@@ -294,6 +412,10 @@ pub struct Parser {
     /// containing a lhs. This is important so that the parser stops at the
     /// comma in between lhs and rhs.
     parsing_pledge_with_lhs: bool,
+    /// A flag to denote that the parser is currently parsing the rhs of an
+    /// implication. This is important so that the parser stops at `<==>`,
+    /// which has weaker binding than `==>`.
+    stop_at_iff: bool,
 }
 
 impl Parser {
@@ -301,23 +423,27 @@ impl Parser {
         let input = ParserStream::from_token_stream(tokens);
         Self {
             input,
+            disjuncts: Vec::new(),
             conjuncts: Vec::new(),
             expr: Vec::new(),
             previous_expression_resolved: false,
             expected_operator: false,
             expected_only_operator: false,
             parsing_pledge_with_lhs: false,
+            stop_at_iff: false,
         }
     }
     fn from_parser_stream(input: ParserStream) -> Self {
         Self {
             input,
+            disjuncts: Vec::new(),
             conjuncts: Vec::new(),
             expr: Vec::new(),
             previous_expression_resolved: false,
             expected_operator: false,
             expected_only_operator: false,
             parsing_pledge_with_lhs: false,
+            stop_at_iff: false,
         }
     }
     fn resolve_and(&mut self) -> syn::Result<()>{
@@ -349,8 +475,19 @@ impl Parser {
         self.expected_only_operator = false;
         Ok(())
     }
-    fn resolve_implies(&mut self) -> syn::Result<AssertionWithoutId>{
-        // handles the case when there is no lhs of the ==> operator
+    fn resolve_or(&mut self) -> syn::Result<()>{
+        // the lhs of the || operator is resolved in the same way as the lhs of the &&
+        // operator; afterwards, the conjunction parsed so far becomes a disjunct, which
+        // makes disjunction weaker than conjunction
+        self.resolve_and()?;
+        let conjunction = self.conjuncts_to_assertion()?;
+        self.disjuncts.push(conjunction);
+        Ok(())
+    }
+    /// Finish parsing the lhs of a binary operator that is weaker than
+    /// disjunction (`==>` or `<==>`) and return it.
+    fn resolve_weak_operator_lhs(&mut self) -> syn::Result<AssertionWithoutId>{
+        // handles the case when there is no lhs of the operator
         if !self.expected_operator {
             return Err(self.error_expected_assertion());
         }
@@ -366,38 +503,153 @@ impl Parser {
             }
         }
 
-        // handles the case when there is no rhs of the ==> operator
+        // handles the case when there is no rhs of the operator
         if self.input.is_empty() {
             return Err(self.error_expected_assertion());
         }
 
-        // recursively parse the rhs assertion; note that this automatically handles the
-        // operator precedence: implication will be then weaker than conjunction
+        self.disjuncts_to_assertion()
+    }
+    /// Recursively parse the rest of the input as the rhs of a binary operator.
+    /// If `stop_at_iff` is set, the parsing stops at the first `<==>`, which is
+    /// then left in the input.
+    fn parse_weak_operator_rhs(&mut self, stop_at_iff: bool) -> syn::Result<AssertionWithoutId>{
         let mut parser = Parser::from_parser_stream(
             mem::replace(&mut self.input, ParserStream::empty())
         );
+        parser.parsing_pledge_with_lhs = self.parsing_pledge_with_lhs;
+        parser.stop_at_iff = stop_at_iff;
+        let rhs = parser.extract_assertion();
+        self.input = mem::replace(&mut parser.input, ParserStream::empty());
+        rhs
+    }
+    fn resolve_implies(&mut self) -> syn::Result<AssertionWithoutId>{
+        let lhs = self.resolve_weak_operator_lhs()?;
 
-        let lhs = self.conjuncts_to_assertion();
-        if let Err(err) = lhs {
-            return Err(err);
+        // recursively parse the rhs assertion; note that this automatically handles the
+        // operator precedence: implication will be then weaker than conjunction and
+        // disjunction
+        let rhs = self.parse_weak_operator_rhs(true)?;
+
+        let implication = AssertionWithoutId{
+            kind: box common::AssertionKind::Implies(lhs, rhs)
+        };
+
+        // the rhs stopped at `<==>`, which is weaker than the implication
+        if !self.stop_at_iff && self.input.check_and_consume_operator("<==>") {
+            return self.resolve_iff(implication);
         }
 
-        let rhs = parser.extract_assertion();
-        if let Err(err) = rhs {
-            return Err(err);
+        Ok(implication)
+    }
+    fn resolve_iff(&mut self, lhs: AssertionWithoutId) -> syn::Result<AssertionWithoutId>{
+        // handles the case when there is no rhs of the <==> operator
+        if self.input.is_empty() {
+            return Err(self.error_expected_assertion());
         }
 
-        return Ok(AssertionWithoutId{
-            kind: box common::AssertionKind::Implies(lhs.unwrap(), rhs.unwrap())
-        });
+        // recursively parse the rhs assertion, which makes <==> right-associative
+        let rhs = self.parse_weak_operator_rhs(false)?;
+
+        Ok(AssertionWithoutId{
+            kind: box common::AssertionKind::Iff(lhs, rhs)
+        })
     }
     fn resolve_forall(&mut self) -> syn::Result<()> {
+        let (vars, trigger_set, body) = self.parse_quantifier()?;
+        let conjunct = AssertionWithoutId {
+            kind: box common::AssertionKind::ForAll(vars, trigger_set, body)
+        };
+        self.push_resolved_conjunct(conjunct);
+        Ok(())
+    }
+    fn resolve_exists(&mut self) -> syn::Result<()> {
+        let (vars, trigger_set, body) = self.parse_quantifier()?;
+        let conjunct = AssertionWithoutId {
+            kind: box common::AssertionKind::Exists(vars, trigger_set, body)
+        };
+        self.push_resolved_conjunct(conjunct);
+        Ok(())
+    }
+    fn resolve_not(&mut self) -> syn::Result<()> {
         if self.expected_operator {
             return Err(self.error_expected_operator());
         }
 
-        // check whether there is a parenthesized block after forall
-        if let Some(group) = self.input.check_and_consume_parenthesized_block() {
+        // `peek_negated_assertion` already checked the shape of the negated assertion
+        let len = self.input.negated_assertion_len(0).unwrap();
+        self.input.pop();
+        let mut operand = TokenStream::new();
+        for _ in 1..len {
+            operand.extend(self.input.pop());
+        }
+
+        let mut parser = Parser::from_token_stream(operand);
+        let operand = parser.extract_assertion()?;
+        self.push_resolved_conjunct(AssertionWithoutId {
+            kind: box common::AssertionKind::Not(operand)
+        });
+        Ok(())
+    }
+    fn resolve_type_cond(&mut self) -> syn::Result<()> {
+        if self.expected_operator {
+            return Err(self.error_expected_operator());
+        }
+
+        // `peek_type_condition` already checked the shape of the type condition
+        let len = self.input.type_condition_len().unwrap();
+        self.input.pop();
+        let start = self.input.span;
+        let mut bounds = TokenStream::new();
+        for _ in 0..len {
+            bounds.extend(self.input.pop());
+        }
+        self.input.pop();
+        let span = start.join(self.input.span).unwrap_or(start);
+        let all_args: ForAllArgs = syn::parse2(bounds)?;
+        self.input.check_and_consume_operator("==>");
+
+        // handles the case when there is no rhs of the type condition
+        if self.input.is_empty() {
+            return Err(self.error_expected_assertion());
+        }
+
+        // the body extends as far as the rhs of an implication would
+        let body = self.parse_weak_operator_rhs(true)?;
+        let vars = ForAllVars {
+            spec_id: common::SpecificationId::dummy(),
+            id: (),
+            vars: all_args.args.into_iter().collect(),
+            span,
+        };
+        self.push_resolved_conjunct(AssertionWithoutId {
+            kind: box common::AssertionKind::TypeCond(vars, body)
+        });
+        Ok(())
+    }
+    fn push_resolved_conjunct(&mut self, conjunct: AssertionWithoutId) {
+        self.conjuncts.push(conjunct);
+        self.previous_expression_resolved = true;
+        self.expected_only_operator = true;
+        self.expected_operator = true;
+    }
+    /// Parse the parenthesized part of a quantifier, which is shared by
+    /// `forall` and `exists`.
+    fn parse_quantifier(&mut self) -> syn::Result<QuantifierWithoutId> {
+        if self.expected_operator {
+            return Err(self.error_expected_operator());
+        }
+
+        // the span of the keyword
+        let keyword_span = self.input.span;
+
+        // check whether there is a parenthesized block after the quantifier
+        let group = match self.input.check_and_consume_parenthesized_block() {
+            Some(group) => group,
+            None => return Err(self.error_expected_parenthesis()),
+        };
+        {
+            let span = keyword_span.join(group.span()).unwrap_or(keyword_span);
 
             // construct a ParserStream off of the parenthesized block for further parsing
             let mut stream = ParserStream::from_token_stream(group.stream());
@@ -472,26 +724,13 @@ impl Parser {
                 trigger_set = TriggerSet(vec_of_triggers);
             }
 
-            let conjunct = AssertionWithoutId {
-                kind: box common::AssertionKind::ForAll(
-                    ForAllVars {
-                        spec_id: common::SpecificationId::dummy(),
-                        id: (),
-                        vars
-                    },
-                    trigger_set,
-                    body,
-                )
+            let vars = ForAllVars {
+                spec_id: common::SpecificationId::dummy(),
+                id: (),
+                vars,
+                span,
             };
-
-            self.conjuncts.push(conjunct);
-            self.previous_expression_resolved = true;
-            self.expected_only_operator = true;
-            self.expected_operator = true;
-            return Ok(());
-        }
-        else {
-            return Err(self.error_expected_parenthesis());
+            Ok((vars, trigger_set, body))
         }
     }
     fn resolve_parenthesized_block(&mut self, group: Group) -> syn::Result<()>{
@@ -541,9 +780,32 @@ impl Parser {
         self.expected_operator = true;
         Ok(())
     }
+    /// Remove the message of a clause, a string literal after a trailing
+    /// comma as in `requires(x > 0, "x is positive")`, from the input and
+    /// return it. Must be called before the assertion of the clause is
+    /// extracted, since nested assertions cannot have messages.
+    pub fn extract_message(&mut self) -> syn::Result<Option<String>> {
+        // attributes collected from an item still contain the parentheses
+        // around their arguments
+        if let (1, Some(TokenTree::Group(group))) = (self.input.tokens.len(), self.input.tokens.front()) {
+            let inner = ParserStream::from_token_stream(group.stream());
+            if group.delimiter() == Delimiter::Parenthesis && inner.clone().pop_message().is_some() {
+                self.input = inner;
+            }
+        }
+        let message = match self.input.pop_message() {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        if self.input.is_empty() {
+            return Err(syn::Error::new(message.span(), "expected an assertion before the message"));
+        }
+        Ok(Some(message.value()))
+    }
     /// Creates a single Prusti assertion from the input and returns it.
     pub fn extract_assertion(&mut self) -> syn::Result<AssertionWithoutId> {
-        // detect possibly ambiguous input
+        // detect possibly ambiguous input: `&&` and `||` are only mixed in
+        // parenthesized subexpressions
         if self.input.contains_both_and_or() {
             return Err(self.error_ambiguous_expression());
         }
@@ -555,14 +817,43 @@ impl Parser {
                     return Err(err);
                 }
             }
+            else if self.input.check_and_consume_operator("||") {
+                if let Err(err) = self.resolve_or() {
+                    return Err(err);
+                }
+            }
+            else if self.stop_at_iff && self.input.peek_operator("<==>") {
+                break;
+            }
+            else if self.input.check_and_consume_operator("<==>") {
+                let lhs = self.resolve_weak_operator_lhs()?;
+                return self.resolve_iff(lhs);
+            }
             else if self.input.check_and_consume_operator("==>") {
                 return self.resolve_implies();
             }
-            else if self.input.check_and_consume_keyword("forall") {
+            else if self.expr.is_empty() && self.input.peek_negated_assertion() {
+                if let Err(err) = self.resolve_not() {
+                    return Err(err);
+                }
+            }
+            else if self.expr.is_empty() && self.input.peek_type_condition() {
+                if let Err(err) = self.resolve_type_cond() {
+                    return Err(err);
+                }
+            }
+            else if self.expr.is_empty() && self.input.peek_quantifier("forall") {
+                self.input.pop();
                 if let Err(err) = self.resolve_forall() {
                     return Err(err);
                 }
             }
+            else if self.expr.is_empty() && self.input.peek_quantifier("exists") {
+                self.input.pop();
+                if let Err(err) = self.resolve_exists() {
+                    return Err(err);
+                }
+            }
             else if let Some(group) = self.input.check_and_consume_parenthesized_block() {
                 if let Err(err) = self.resolve_parenthesized_block(group) {
                     return Err(err);
@@ -585,8 +876,8 @@ impl Parser {
             }
         }
 
-        // build a conjunction off of the assertions parsed
-        self.conjuncts_to_assertion()
+        // build a disjunction of conjunctions off of the assertions parsed
+        self.disjuncts_to_assertion()
     }
     fn parse_rust_expression(&mut self, tokens: TokenStream) -> syn::Result<syn::Expr> {
         let maybe_expr = syn::parse2(tokens.clone());
@@ -652,6 +943,23 @@ impl Parser {
             })
         }
     }
+    /// Convert all disjuncts, including the conjunction that is currently being
+    /// parsed, into Or assertion.
+    fn disjuncts_to_assertion(&mut self) -> syn::Result<AssertionWithoutId> {
+        let conjunction = self.conjuncts_to_assertion()?;
+
+        // if there is no disjunction, just return the conjunction
+        if self.disjuncts.is_empty() {
+            Ok(conjunction)
+        }
+        else{
+            let mut disjuncts = mem::replace(&mut self.disjuncts, Vec::new());
+            disjuncts.push(conjunction);
+            Ok(AssertionWithoutId{
+                kind: box common::AssertionKind::Or(disjuncts)
+            })
+        }
+    }
     /// Convert parsed Rust expression into a Prusti conjunct.
     fn convert_expr_into_conjunct(&mut self) -> syn::Result<()> {
         let expr = self.expr.clone();
@@ -675,11 +983,17 @@ impl Parser {
         syn::Error::new(self.input.span,
                         "`==>` cannot be part of Rust expression")
     }
+    fn error_ambiguous_expression(&self) -> syn::Error {
+        syn::Error::new(
+            self.input.span,
+            "found `||` and `&&` in the same subexpression. \
+            Hint: add parentheses to clarify the evaluation order.")
+    }
     fn error_expected_assertion(&self) -> syn::Error {
         syn::Error::new(self.input.span, "expected Prusti assertion")
     }
     fn error_expected_operator(&self) -> syn::Error {
-        syn::Error::new(self.input.span, "expected `&&` or `==>`")
+        syn::Error::new(self.input.span, "expected `&&`, `||`, `==>` or `<==>`")
     }
     fn error_expected_parenthesis(&self) -> syn::Error {
         syn::Error::new(self.input.span, "expected `(`")
@@ -699,12 +1013,6 @@ impl Parser {
     fn error_expected_tuple(&self) -> syn::Error {
         syn::Error::new(self.input.span, "`triggers` must be an array of tuples containing Rust expressions")
     }
-    fn error_ambiguous_expression(&self) -> syn::Error {
-        syn::Error::new(
-            self.input.span,
-            "found `||` and `&&` in the same subexpression. \
-            Hint: add parentheses to clarify the evaluation order.")
-    }
     fn error_no_quantifier_arguments(&self) -> syn::Error {
         syn::Error::new(self.input.span, "a quantifier must have at least one argument")
     }
@@ -1,5 +1,5 @@
 use super::common::{self, ExpressionIdGenerator};
-use proc_macro2::{TokenStream, Span, Spacing, Punct};
+use proc_macro2::{Delimiter, Group, TokenStream, TokenTree, Span, Spacing, Punct};
 use quote::{quote_spanned, ToTokens, TokenStreamExt};
 use std::collections::HashMap;
 use syn::parse::{Parse, ParseStream};
@@ -29,6 +29,8 @@ pub type Expression = common::Expression<ExpressionId, syn::Expr>;
 pub type TriggerSet = common::TriggerSet<ExpressionId, syn::Expr>;
 /// A pledge that has not types associated with it.
 pub type Pledge = common::Pledge<ExpressionId, syn::Expr, Arg>;
+/// A termination measure that has no types associated with it.
+pub type Variant = common::Variant<ExpressionId, syn::Expr>;
 
 /// An abstraction over all kinds of function items.
 pub enum AnyFnItem {
@@ -84,15 +86,239 @@ impl ToTokens for AnyFnItem {
     }
 }
 
+/// A function marked with `#[predicate]`. Its assertion is either its body or,
+/// if it has no body, the argument of the attribute. The assertion is in
+/// general not a valid Rust expression, so it is split off before the rest of
+/// the function is parsed.
+pub struct PredicateFnItem {
+    /// The function with an empty body.
+    pub item: syn::ItemFn,
+    /// The tokens of the assertion.
+    pub assertion: TokenStream,
+}
+
+impl PredicateFnItem {
+    /// Parse the function that `#[predicate]` with the arguments `attr` is
+    /// attached to.
+    pub fn parse(attr: TokenStream, item_tokens: TokenStream) -> syn::Result<Self> {
+        let mut tokens: Vec<TokenTree> = item_tokens.into_iter().collect();
+        let body = match tokens.pop() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                Some(group.stream())
+            }
+            Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => None,
+            _ => return Err(syn::Error::new(
+                Span::call_site(),
+                "`#[predicate]` can only be attached to functions",
+            )),
+        };
+        tokens.push(TokenTree::Group(Group::new(Delimiter::Brace, TokenStream::new())));
+        let item: syn::ItemFn = syn::parse2(tokens.into_iter().collect())?;
+        match &item.sig.output {
+            syn::ReturnType::Type(_, box syn::Type::Path(syn::TypePath { qself: None, path }))
+                if path.is_ident("bool") => {}
+            output => return Err(syn::Error::new(
+                if let syn::ReturnType::Default = output { item.sig.span() } else { output.span() },
+                "a predicate must return `bool`",
+            )),
+        }
+        let assertion = match (body, attr.is_empty()) {
+            (Some(body), true) => body,
+            (None, false) => attr,
+            (Some(_), false) => return Err(syn::Error::new_spanned(
+                attr,
+                "the assertion of a predicate is either its body or the argument of \
+                 `#[predicate]`, not both",
+            )),
+            (None, true) => return Err(syn::Error::new(
+                item.sig.ident.span(),
+                "a predicate must have a body or an assertion as the argument of `#[predicate]`",
+            )),
+        };
+        Ok(Self { item, assertion })
+    }
+}
+
+/// An abstraction over all kinds of type items that can have an invariant.
+pub enum AnyTypeItem {
+    Struct(syn::ItemStruct),
+    Enum(syn::ItemEnum),
+}
+
+impl syn::parse::Parse for AnyTypeItem {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let item: syn::Item = input.parse()?;
+        match item {
+            syn::Item::Struct(item_struct) => Ok(AnyTypeItem::Struct(item_struct)),
+            syn::Item::Enum(item_enum) => Ok(AnyTypeItem::Enum(item_enum)),
+            _ => Err(syn::Error::new(
+                item.span(),
+                "type invariants can only be attached to structs and enums",
+            )),
+        }
+    }
+}
+
+impl AnyTypeItem {
+    pub fn attrs_mut(&mut self) -> &mut Vec<syn::Attribute> {
+        match self {
+            AnyTypeItem::Struct(item) => &mut item.attrs,
+            AnyTypeItem::Enum(item) => &mut item.attrs,
+        }
+    }
+    pub fn ident(&self) -> &syn::Ident {
+        match self {
+            AnyTypeItem::Struct(item) => &item.ident,
+            AnyTypeItem::Enum(item) => &item.ident,
+        }
+    }
+    pub fn generics(&self) -> &syn::Generics {
+        match self {
+            AnyTypeItem::Struct(item) => &item.generics,
+            AnyTypeItem::Enum(item) => &item.generics,
+        }
+    }
+}
+
+impl ToTokens for AnyTypeItem {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            AnyTypeItem::Struct(item) => item.to_tokens(tokens),
+            AnyTypeItem::Enum(item) => item.to_tokens(tokens),
+        }
+    }
+}
+
 impl Assertion {
+    /// Parse a clause, returning its assertion and its message, if any.
     pub(crate) fn parse(
         tokens: TokenStream,
         spec_id: SpecificationId,
         id_generator: &mut ExpressionIdGenerator,
-    ) -> syn::Result<Self> {
+    ) -> syn::Result<(Self, Option<String>)> {
         let mut parser = Parser::from_token_stream(tokens);
+        let message = parser.extract_message()?;
         let assertion = parser.extract_assertion()?;
-        Ok(assertion.assign_id(spec_id, id_generator))
+        Ok((assertion.assign_id(spec_id, id_generator), message))
+    }
+
+    /// Collect the type conditions of the assertion, including nested ones.
+    /// The body of each of them is type-checked in a separate spec item that
+    /// has the bounds, so that the rest of the assertion does not depend on
+    /// them.
+    pub(crate) fn type_conditions(&self) -> Vec<TypeCondition<'_>> {
+        let mut conditions = Vec::new();
+        self.collect_type_conditions(&[], &[], &mut conditions);
+        conditions
+    }
+
+    fn collect_type_conditions<'a>(
+        &'a self,
+        predicates: &[syn::WherePredicate],
+        bound_vars: &[&'a [Arg]],
+        conditions: &mut Vec<TypeCondition<'a>>,
+    ) {
+        match &*self.kind {
+            AssertionKind::Expr(_) => {}
+            AssertionKind::And(assertions) | AssertionKind::Or(assertions) => {
+                for assertion in assertions {
+                    assertion.collect_type_conditions(predicates, bound_vars, conditions);
+                }
+            }
+            AssertionKind::Not(assertion) => {
+                assertion.collect_type_conditions(predicates, bound_vars, conditions);
+            }
+            AssertionKind::Implies(lhs, rhs) | AssertionKind::Iff(lhs, rhs) => {
+                lhs.collect_type_conditions(predicates, bound_vars, conditions);
+                rhs.collect_type_conditions(predicates, bound_vars, conditions);
+            }
+            AssertionKind::TypeCond(vars, body) => {
+                let mut predicates = predicates.to_vec();
+                for var in &vars.vars {
+                    let Arg { name, typ } = var;
+                    predicates.push(syn::parse_quote! { #name: #typ });
+                }
+                body.collect_type_conditions(&predicates, bound_vars, conditions);
+                conditions.push(TypeCondition {
+                    vars,
+                    body,
+                    predicates,
+                    bound_vars: bound_vars.to_vec(),
+                });
+            }
+            AssertionKind::ForAll(vars, _, body) | AssertionKind::Exists(vars, _, body) => {
+                let mut bound_vars = bound_vars.to_vec();
+                bound_vars.push(&vars.vars);
+                body.collect_type_conditions(predicates, &bound_vars, conditions);
+            }
+        }
+    }
+
+    /// The span of the source code from which the assertion was parsed.
+    /// The spans of nested assertions are joined, so that for example the
+    /// span of an implication covers both operands.
+    pub(crate) fn span(&self) -> Span {
+        let join = |first: Span, last: Span| first.join(last).unwrap_or(first);
+        match &*self.kind {
+            AssertionKind::Expr(expr) => expr.expr.span(),
+            AssertionKind::And(assertions) | AssertionKind::Or(assertions) => {
+                match (assertions.first(), assertions.last()) {
+                    (Some(first), Some(last)) => join(first.span(), last.span()),
+                    _ => Span::call_site(),
+                }
+            }
+            AssertionKind::Not(assertion) => assertion.span(),
+            AssertionKind::Implies(lhs, rhs) | AssertionKind::Iff(lhs, rhs) => {
+                join(lhs.span(), rhs.span())
+            }
+            AssertionKind::ForAll(vars, _, body)
+            | AssertionKind::Exists(vars, _, body)
+            | AssertionKind::TypeCond(vars, body) => join(vars.span, body.span()),
+        }
+    }
+}
+
+/// A type condition `<T: Trait> ==> A` of an assertion.
+pub(crate) struct TypeCondition<'a> {
+    pub vars: &'a ForAllVars<ExpressionId, Arg>,
+    pub body: &'a Assertion,
+    /// The bounds of the type condition and of the type conditions that it
+    /// is nested in.
+    pub predicates: Vec<syn::WherePredicate>,
+    /// The variables of the quantifiers that the type condition is nested
+    /// in, outermost first.
+    pub bound_vars: Vec<&'a [Arg]>,
+}
+
+impl TypeCondition<'_> {
+    /// The identifier with which the type condition is referred to, both in
+    /// the serialized assertion and by the spec item that type-checks it.
+    pub(crate) fn identifier(&self) -> String {
+        format!("{}_{}", self.vars.spec_id, self.vars.id)
+    }
+
+    /// Type-check the body of the type condition, in the scope of the
+    /// variables of the enclosing quantifiers.
+    pub(crate) fn encode_type_check(&self) -> TokenStream {
+        let span = Span::call_site();
+        let identifier = self.identifier();
+        let mut nested_assertion = TokenStream::new();
+        self.body.encode_type_check(&mut nested_assertion);
+        let mut typeck_call = quote_spanned! { span =>
+            #[prusti::spec_only]
+            #[prusti::expr_id = #identifier]
+            || {
+                #nested_assertion
+            };
+        };
+        for vars in self.bound_vars.iter().rev() {
+            typeck_call = quote_spanned! { span =>
+                |#(#vars),*| {
+                    #typeck_call
+                };
+            };
+        }
+        typeck_call
     }
 }
 
@@ -117,14 +343,50 @@ impl Parse for common::Assertion<(), syn::Expr, Arg> {
     }
 }
 
+impl Variant {
+    /// Parse a comma-separated list of expressions. A single tuple is
+    /// treated as the list of its elements, so that `decreases((a, b))` is
+    /// the same as `decreases(a, b)`.
+    pub(crate) fn parse(
+        tokens: TokenStream,
+        spec_id: SpecificationId,
+        id_generator: &mut ExpressionIdGenerator,
+    ) -> syn::Result<Self> {
+        let parser = syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+        let mut exprs: Vec<syn::Expr> = syn::parse::Parser::parse2(parser, tokens.clone())?
+            .into_iter()
+            .collect();
+        if exprs.is_empty() {
+            return Err(syn::Error::new(
+                tokens.span(),
+                "a termination measure must have at least one expression",
+            ));
+        }
+        if let [syn::Expr::Tuple(tuple)] = exprs.as_slice() {
+            exprs = tuple.elems.iter().cloned().collect();
+        }
+        Ok(Variant {
+            exprs: exprs.into_iter()
+                .map(|expr| Expression {
+                    spec_id,
+                    id: id_generator.generate(),
+                    expr,
+                })
+                .collect(),
+        })
+    }
+}
+
 impl Pledge {
+    /// Parse a pledge, returning it and its message, if any.
     pub(crate) fn parse(
         tokens: TokenStream,
         spec_id_lhs: Option<SpecificationId>,
         spec_id_rhs: SpecificationId,
         id_generator: &mut ExpressionIdGenerator,
-    ) -> syn::Result<Self> {
+    ) -> syn::Result<(Self, Option<String>)> {
         let mut parser = Parser::from_token_stream(tokens);
+        let message = parser.extract_message()?;
         let pledge = if let Some(spec_id_lhs) = spec_id_lhs {
             let pledge = parser.extract_pledge()?;
             Pledge {
@@ -142,7 +404,7 @@ impl Pledge {
                 rhs: pledge.rhs.assign_id(spec_id_rhs, id_generator),
             }
         };
-        Ok(pledge)
+        Ok((pledge, message))
     }
 }
 
@@ -203,7 +465,8 @@ impl AssignExpressionId<ForAllVars<ExpressionId, Arg>> for common::ForAllVars<()
         ForAllVars {
             spec_id,
             id: id_generator.generate(),
-            vars: self.vars
+            vars: self.vars,
+            span: self.span,
         }
     }
 }
@@ -243,16 +506,33 @@ impl AssignExpressionId<AssertionKind> for common::AssertionKind<(), syn::Expr,
                           .map(|assertion|
                               Assertion { kind: assertion.kind.assign_id(spec_id, id_generator) })
                           .collect()),
+            Or(assertions) => Or(
+                assertions.into_iter()
+                          .map(|assertion| assertion.assign_id(spec_id, id_generator))
+                          .collect()),
+            Not(assertion) => Not(assertion.assign_id(spec_id, id_generator)),
             Implies(lhs, rhs) => Implies(
                 lhs.assign_id(spec_id, id_generator),
                 rhs.assign_id(spec_id, id_generator)
             ),
+            Iff(lhs, rhs) => Iff(
+                lhs.assign_id(spec_id, id_generator),
+                rhs.assign_id(spec_id, id_generator)
+            ),
+            TypeCond(vars, body) => TypeCond(
+                vars.assign_id(spec_id, id_generator),
+                body.assign_id(spec_id, id_generator)
+            ),
             ForAll(vars, triggers, body) => ForAll(
                 vars.assign_id(spec_id, id_generator),
                 triggers.assign_id(spec_id, id_generator),
                 body.assign_id(spec_id, id_generator)
             ),
-            x => unimplemented!("{:?}", x),
+            Exists(vars, triggers, body) => Exists(
+                vars.assign_id(spec_id, id_generator),
+                triggers.assign_id(spec_id, id_generator),
+                body.assign_id(spec_id, id_generator)
+            ),
         }
     }
 }
@@ -332,16 +612,20 @@ impl EncodeTypeCheck for Assertion {
             AssertionKind::Expr(expression) => {
                 expression.encode_type_check(tokens);
             }
-            AssertionKind::And(assertions) => {
+            AssertionKind::And(assertions) | AssertionKind::Or(assertions) => {
                 for assertion in assertions {
                     assertion.encode_type_check(tokens);
                 }
             }
-            AssertionKind::Implies(lhs, rhs) => {
+            AssertionKind::Not(assertion) => {
+                assertion.encode_type_check(tokens);
+            }
+            AssertionKind::Implies(lhs, rhs) | AssertionKind::Iff(lhs, rhs) => {
                 lhs.encode_type_check(tokens);
                 rhs.encode_type_check(tokens);
             }
-            AssertionKind::ForAll(vars, triggers, body) => {
+            AssertionKind::ForAll(vars, triggers, body) |
+            AssertionKind::Exists(vars, triggers, body) => {
                 let vec_of_vars = &vars.vars;
                 let span = Span::call_site();
                 let identifier = format!("{}_{}", vars.spec_id, vars.id);
@@ -359,13 +643,32 @@ impl EncodeTypeCheck for Assertion {
                 };
                 tokens.extend(typeck_call);
             }
-            x => {
-                unimplemented!("{:?}", x);
+            AssertionKind::TypeCond(..) => {
+                // the body is type-checked in a separate spec item that has
+                // the bounds, see `Assertion::type_conditions`
             }
         }
     }
 }
 
+impl EncodeTypeCheck for Variant {
+    fn encode_type_check(&self, tokens: &mut TokenStream) {
+        // unlike assertions, the components of a measure are not of type `bool`
+        for expression in &self.exprs {
+            let span = expression.expr.span();
+            let expr = &expression.expr;
+            let identifier = format!("{}_{}", expression.spec_id, expression.id);
+            tokens.extend(quote_spanned! { span =>
+                #[prusti::spec_only]
+                #[prusti::expr_id = #identifier]
+                || {
+                    #expr
+                };
+            });
+        }
+    }
+}
+
 impl EncodeTypeCheck for Expression {
     fn encode_type_check(&self, tokens: &mut TokenStream) {
         let span = self.expr.span();
@@ -0,0 +1,342 @@
+//! Snapshot tests for the desugaring of specifications.
+//!
+//! Every case is expanded on token streams, without the compiler, and the
+//! result is compared with `tests/expansion/<name>.expanded`. The IDs of
+//! specifications and expressions are replaced by `ID1`, `ID2`, ... in the
+//! order in which they appear, so that the snapshots do not depend on how
+//! the IDs are derived. If `expansions_match_snapshots` fails because the
+//! desugaring was changed on purpose, review the differences and regenerate
+//! the snapshots by running the tests with `PRUSTI_BLESS_FIXTURES=1`.
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use prusti_specs::{rewrite_prusti_attributes, SpecAttributeKind};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// The entry point of `prusti-specs` that a case is expanded with.
+enum Macro {
+    /// A specification attribute, with its arguments and the item.
+    Attribute(SpecAttributeKind, &'static str, &'static str),
+    BodyInvariant(&'static str),
+    BodyVariant(&'static str),
+    Closure(&'static str),
+    /// `refine_trait_spec`, with its arguments and the impl block.
+    RefineTraitSpec(&'static str, &'static str),
+    ExternSpec(&'static str),
+}
+
+const CASES: &[(&str, Macro)] = &[
+    (
+        "requires_ensures",
+        Macro::Attribute(
+            SpecAttributeKind::Requires,
+            "x > 0",
+            "#[ensures(result > x)] fn f(x: u32) -> u32 { x + 1 }",
+        ),
+    ),
+    (
+        "messages",
+        Macro::Attribute(
+            SpecAttributeKind::Requires,
+            "x < 10, \"x is a digit\"",
+            "#[ensures(result > x, \"grows\")] fn f(x: u32) -> u32 { x + 1 }",
+        ),
+    ),
+    (
+        "quantifiers",
+        Macro::Attribute(
+            SpecAttributeKind::Requires,
+            "forall(|i: usize| i < a.len() ==> a[i] > 0, triggers = [(a[i],)]) \
+             && !exists(|i: usize| i < a.len() && a[i] == 0)",
+            "fn f(a: &[u32]) {}",
+        ),
+    ),
+    (
+        "type_cond",
+        Macro::Attribute(SpecAttributeKind::Ensures, "<T: Copy> ==> result", "fn f<T>(x: T) -> bool { true }"),
+    ),
+    (
+        "after_expiry",
+        Macro::Attribute(
+            SpecAttributeKind::AfterExpiry,
+            "result => *x > 0",
+            "#[after_expiry_if(*x > 1, *x > 2)] fn f(x: &mut u32) -> &mut u32 { x }",
+        ),
+    ),
+    (
+        "pure_trusted",
+        Macro::Attribute(
+            SpecAttributeKind::Pure,
+            "",
+            "#[trusted(reason = \"checked by hand\", ticket = \"SEC-1\")] fn f() -> u32 { 0 }",
+        ),
+    ),
+    (
+        "trusted_mod",
+        Macro::Attribute(
+            SpecAttributeKind::Trusted,
+            "",
+            "mod ffi { \
+                fn a() -> u8 { 0 } \
+                #[trusted(false)] fn b() {} \
+                #[prusti_contracts::trusted(false)] fn c() {} \
+                impl S { fn d(&self) {} } \
+            }",
+        ),
+    ),
+    (
+        "decreases",
+        Macro::Attribute(SpecAttributeKind::Decreases, "n, m", "fn f(n: u32, m: u32) {}"),
+    ),
+    (
+        "invariant",
+        Macro::Attribute(SpecAttributeKind::Invariant, "self.x > 0", "struct S<T: Copy> { x: u32, t: T }"),
+    ),
+    (
+        "predicate",
+        Macro::Attribute(
+            SpecAttributeKind::Predicate,
+            "",
+            "fn is_square(x: u32) -> bool { exists(|i: u32| i <= x && i * i == x) }",
+        ),
+    ),
+    (
+        "predicate_argument",
+        Macro::Attribute(
+            SpecAttributeKind::Predicate,
+            "forall(|i: usize| i < v.len() ==> v[i] > 0)",
+            "fn all_positive(v: &[u32]) -> bool;",
+        ),
+    ),
+    ("body_invariant", Macro::BodyInvariant("i < 10 && forall(|j: usize| j < i ==> a[j] > 0)")),
+    ("body_variant", Macro::BodyVariant("(n - i, m)")),
+    ("closure", Macro::Closure("requires(x > 0), ensures(result > x), |x: u32| -> u32 { x + 1 }")),
+    (
+        "refine_trait_spec",
+        Macro::RefineTraitSpec(
+            "check_subtyping",
+            "impl Iterator for Counter { \
+                type Item = u32; \
+                #[requires(self.count < 10)] \
+                #[ensures(result.is_some())] \
+                fn next(&mut self) -> Option<Self::Item> { None } \
+            }",
+        ),
+    ),
+    (
+        "refine_trait_spec_impl_trait",
+        Macro::RefineTraitSpec(
+            "check_subtyping",
+            "impl<K> InsertAll<K> for Set<K> { \
+                #[ensures(self.len() >= old(self.len()))] \
+                fn insert_all<H: Hasher>(&mut self, hasher: H, keys: impl IntoIterator<Item = K>) {} \
+            }",
+        ),
+    ),
+    (
+        "refine_trait_spec_extend",
+        Macro::RefineTraitSpec(
+            "mode = \"extend\"",
+            "impl<T: Copy> Get<T> for Wrapper<T> { #[ensures(result == self.0)] fn get(&self) -> T { self.0 } }",
+        ),
+    ),
+    (
+        "refine_trait_spec_associated_items",
+        Macro::RefineTraitSpec(
+            "",
+            "impl Buffer for Ring { \
+                type Item = u8; \
+                const CAPACITY: usize = 16; \
+                #[requires(self.len() < Self::CAPACITY && self.len() < Self::DEFAULT_LIMIT)] \
+                #[ensures(result == old(item))] \
+                fn push(&mut self, item: Self::Item) -> Self::Item { item } \
+            }",
+        ),
+    ),
+    (
+        "extern_spec_impl",
+        Macro::ExternSpec(
+            "impl<T> std::vec::Vec<T> { #[ensures(result.len() == 0)] fn new() -> Vec<T>; }",
+        ),
+    ),
+    (
+        "extern_spec_trait_impl",
+        Macro::ExternSpec(
+            "impl<T: Clone> Clone for std::vec::Vec<T> { #[ensures(result.len() == self.len())] fn clone(&self) -> Self; }",
+        ),
+    ),
+    (
+        "extern_spec_trait",
+        Macro::ExternSpec(
+            "trait Default { #[pure] fn default() -> Self; }",
+        ),
+    ),
+    (
+        "extern_spec_mod",
+        Macro::ExternSpec(
+            "mod std { mod mem { #[ensures(*a == old(*b))] pub fn swap<T>(a: &mut T, b: &mut T); } }",
+        ),
+    ),
+];
+
+fn parse(tokens: &str) -> TokenStream {
+    tokens.parse().unwrap()
+}
+
+fn expand(case: &Macro) -> TokenStream {
+    match case {
+        Macro::Attribute(kind, attr, item) => rewrite_prusti_attributes(*kind, parse(attr), parse(item)),
+        Macro::BodyInvariant(tokens) => prusti_specs::body_invariant(parse(tokens)),
+        Macro::BodyVariant(tokens) => prusti_specs::body_variant(parse(tokens)),
+        Macro::Closure(tokens) => prusti_specs::closure(parse(tokens), false),
+        Macro::RefineTraitSpec(attr, item) => prusti_specs::refine_trait_spec(parse(attr), parse(item)),
+        Macro::ExternSpec(item) => prusti_specs::extern_spec(TokenStream::new(), parse(item)),
+    }
+}
+
+/// Print the tokens with every top-level item or statement on its own line.
+fn print(tokens: TokenStream) -> String {
+    let mut output = String::new();
+    let mut line = TokenStream::new();
+    for token in tokens {
+        let ends_line = match &token {
+            TokenTree::Punct(punct) => punct.as_char() == ';',
+            TokenTree::Group(group) => group.delimiter() == Delimiter::Brace,
+            _ => false,
+        };
+        line.extend(Some(token));
+        if ends_line {
+            output.push_str(&line.to_string());
+            output.push('\n');
+            line = TokenStream::new();
+        }
+    }
+    if !line.is_empty() {
+        output.push_str(&line.to_string());
+        output.push('\n');
+    }
+    output
+}
+
+fn is_hex(byte: u8) -> bool {
+    byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte)
+}
+
+/// Whether a hyphenated UUID starts at `start`.
+fn is_uuid_at(bytes: &[u8], start: usize) -> bool {
+    (start == 0 || !bytes[start - 1].is_ascii_alphanumeric())
+        && bytes.len() >= start + 36
+        && (0..36).all(|i| match i {
+            8 | 13 | 18 | 23 => bytes[start + i] == b'-',
+            _ => is_hex(bytes[start + i]),
+        })
+        && bytes.get(start + 36).map_or(true, |byte| !byte.is_ascii_alphanumeric())
+}
+
+/// Replace the IDs by `ID1`, `ID2`, ... in the order of their first
+/// occurrence. An ID is either a hyphenated UUID or 32 hexadecimal digits at
+/// the end of a word, as in the names of generated items. Both forms of an
+/// ID are replaced by the same name.
+fn normalize_ids(text: &str) -> String {
+    // write the UUIDs in their simple form first
+    let bytes = text.as_bytes();
+    let mut simple = String::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        if is_uuid_at(bytes, index) {
+            simple.push_str(&text[index..index + 36].replace('-', ""));
+            index += 36;
+        } else {
+            let c = text[index..].chars().next().unwrap();
+            simple.push(c);
+            index += c.len_utf8();
+        }
+    }
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut output = String::new();
+    let mut word = String::new();
+    let mut flush_word = |word: &mut String, output: &mut String| {
+        let len = word.len();
+        if len >= 32 && word.bytes().skip(len - 32).all(is_hex) {
+            let count = names.len();
+            let name = names.entry(word[len - 32..].to_string())
+                .or_insert_with(|| format!("ID{}", count + 1));
+            output.push_str(&word[..len - 32]);
+            output.push_str(name);
+        } else {
+            output.push_str(word);
+        }
+        word.clear();
+    };
+    for c in simple.chars() {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+        } else {
+            flush_word(&mut word, &mut output);
+            output.push(c);
+        }
+    }
+    flush_word(&mut word, &mut output);
+    output
+}
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("expansion")
+        .join(format!("{}.expanded", name))
+}
+
+#[test]
+fn expansions_match_snapshots() {
+    let bless = std::env::var_os("PRUSTI_BLESS_FIXTURES").is_some();
+    let mut changed = vec![];
+    for (name, case) in CASES {
+        let expanded = normalize_ids(&print(expand(case)));
+        let path = snapshot_path(name);
+        if bless {
+            std::fs::write(&path, &expanded).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("cannot read snapshot `{}`: {}", name, err));
+        if expanded != expected {
+            eprintln!("the expansion of `{}` changed:\n{}", name, expanded);
+            changed.push(*name);
+        }
+    }
+    assert!(changed.is_empty(), "the expansions of {:?} changed", changed);
+}
+
+#[test]
+fn ids_are_normalized_consistently() {
+    let text = "prusti_pre_item_f_0123456789abcdef0123456789abcdef = \
+                \"01234567-89ab-cdef-0123-456789abcdef_101\" \
+                Clonefedcba9876543210fedcba9876543210 fedcba9876543210fedcba9876543210x";
+    assert_eq!(
+        normalize_ids(text),
+        "prusti_pre_item_f_ID1 = \"ID1_101\" CloneID2 fedcba9876543210fedcba9876543210x",
+    );
+}
+
+#[test]
+fn ids_are_deterministic() {
+    for (name, case) in CASES {
+        assert_eq!(
+            print(expand(case)),
+            print(expand(case)),
+            "the expansion of `{}` is not deterministic",
+            name,
+        );
+    }
+}
+
+#[test]
+fn ids_do_not_depend_on_the_position() {
+    let expand_at = |item: &str| print(rewrite_prusti_attributes(
+        SpecAttributeKind::Requires,
+        parse("x > 0"),
+        parse(item),
+    ));
+    assert_eq!(expand_at("fn f(x: u32) {}"), expand_at("\n\n    fn f(x: u32) {}"));
+}
@@ -0,0 +1,170 @@
+//! Golden tests for the format in which specifications are serialized for the
+//! Prusti driver.
+//!
+//! If `encoding_matches_fixtures` fails because the format was changed on
+//! purpose, increase `json::FORMAT_VERSION` and regenerate the fixtures by
+//! running the tests with `PRUSTI_BLESS_FIXTURES=1`.
+
+use prusti_specs::specifications::json::{self, DecodeError, Encoding, FORMAT_VERSION};
+use prusti_specs::{rewrite_prusti_attributes, SpecAttributeKind};
+use std::path::PathBuf;
+
+/// The specifications of the fixtures: the name of the fixture, the
+/// attribute, its arguments and the specified function.
+const CASES: &[(&str, SpecAttributeKind, &str, &str)] = &[
+    ("expr", SpecAttributeKind::Requires, "x > 0", "fn f(x: u32) {}"),
+    ("and_or_not", SpecAttributeKind::Requires, "(x > 0 && !(x > 10)) || x == 20", "fn f(x: u32) {}"),
+    ("implies_iff", SpecAttributeKind::Ensures, "x > 0 ==> result <==> true", "fn f(x: u32) -> bool { true }"),
+    (
+        "forall_triggers",
+        SpecAttributeKind::Requires,
+        "forall(|i: usize, j: usize| i < j ==> a[i] <= a[j], triggers = [(a[i], a[j])])",
+        "fn f(a: &[u32]) {}",
+    ),
+    ("exists", SpecAttributeKind::Ensures, "exists(|i: usize| i < 10 && result == i)", "fn f() -> usize { 0 }"),
+    (
+        "exists_method",
+        SpecAttributeKind::Requires,
+        "p.exists() && exists(|i: usize| i < n)",
+        "fn f(p: &std::path::Path, n: usize) {}",
+    ),
+    ("type_cond", SpecAttributeKind::Requires, "<T: Copy> ==> true", "fn f<T>(x: T) {}"),
+    ("variant", SpecAttributeKind::Decreases, "n, m", "fn f(n: u32, m: u32) {}"),
+    ("variant_tuple", SpecAttributeKind::Decreases, "(n, m + 1)", "fn f(n: u32, m: u32) {}"),
+    ("message", SpecAttributeKind::Requires, "x > 0 && x < 10, \"x is a digit\"", "fn f(x: u32) {}"),
+];
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(format!("{}.json", name))
+}
+
+fn read_fixture(name: &str) -> String {
+    std::fs::read_to_string(fixture_path(name))
+        .unwrap_or_else(|err| panic!("cannot read fixture `{}`: {}", name, err))
+        .trim_end()
+        .to_string()
+}
+
+/// Expand the attribute and return the serialized specifications of the
+/// generated spec items.
+fn serialize(kind: SpecAttributeKind, attr: &str, item: &str) -> Vec<String> {
+    let expanded = rewrite_prusti_attributes(kind, attr.parse().unwrap(), item.parse().unwrap());
+    let file: syn::File = syn::parse2(expanded).unwrap();
+    let mut specs = vec![];
+    for item in file.items {
+        if let syn::Item::Fn(item_fn) = item {
+            for attr in item_fn.attrs {
+                let is_spec = attr.path.segments.len() == 2
+                    && attr.path.segments[0].ident == "prusti"
+                    && (attr.path.segments[1].ident == "assertion"
+                        || attr.path.segments[1].ident == "variant");
+                if let (true, Ok(syn::Meta::NameValue(meta))) = (is_spec, attr.parse_meta()) {
+                    if let syn::Lit::Str(json) = meta.lit {
+                        specs.push(json.value());
+                    }
+                }
+            }
+        }
+    }
+    specs
+}
+
+#[test]
+fn encoding_matches_fixtures() {
+    let bless = std::env::var_os("PRUSTI_BLESS_FIXTURES").is_some();
+    for (name, kind, attr, item) in CASES {
+        let specs = serialize(*kind, attr, item);
+        assert_eq!(specs.len(), 1, "expected one serialized specification for `{}`", name);
+        if bless {
+            std::fs::write(fixture_path(name), format!("{}\n", specs[0])).unwrap();
+        } else {
+            assert_eq!(specs[0], read_fixture(name), "the encoding of `{}` changed", name);
+        }
+    }
+}
+
+#[test]
+fn fixtures_decode() {
+    for (name, kind, ..) in CASES {
+        let json = read_fixture(name);
+        let result = match kind {
+            SpecAttributeKind::Decreases => json::Variant::from_json_string(&json).map(|_| ()),
+            _ => json::Assertion::from_json_string(&json).map(|_| ()),
+        };
+        if let Err(err) = result {
+            panic!("cannot decode fixture `{}`: {}", name, err);
+        }
+    }
+}
+
+#[test]
+fn compact_encoding_round_trips() {
+    for (name, kind, ..) in CASES {
+        let json = read_fixture(name);
+        let (compact, decoded) = match kind {
+            SpecAttributeKind::Decreases => {
+                let compact = json::Variant::from_json_string(&json).unwrap().encode(Encoding::Compact);
+                let decoded = json::Variant::from_json_string(&compact).unwrap().encode(Encoding::Json);
+                (compact, decoded)
+            }
+            _ => {
+                let compact = json::Assertion::from_json_string(&json).unwrap().encode(Encoding::Compact);
+                let decoded = json::Assertion::from_json_string(&compact).unwrap().encode(Encoding::Json);
+                (compact, decoded)
+            }
+        };
+        assert_eq!(decoded, json, "the compact encoding of `{}` does not round-trip", name);
+        // the specifications are embedded in string literals, which escape quotes
+        let literal_len = |spec: &str| format!("{:?}", spec).len();
+        assert!(
+            literal_len(&compact) < literal_len(&json),
+            "the compact encoding of `{}` is not shorter",
+            name,
+        );
+    }
+}
+
+#[test]
+fn unversioned_input_is_rejected() {
+    let json = read_fixture("unversioned");
+    match json::Assertion::from_json_string(&json) {
+        Err(DecodeError::MissingVersion) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("an unversioned specification was accepted"),
+    }
+}
+
+#[test]
+fn other_version_is_rejected() {
+    let json = read_fixture("expr").replacen(
+        &format!("\"version\":{}", FORMAT_VERSION),
+        &format!("\"version\":{}", FORMAT_VERSION + 1),
+        1,
+    );
+    match json::Assertion::from_json_string(&json) {
+        Err(DecodeError::UnsupportedVersion { found }) => assert_eq!(found, FORMAT_VERSION + 1),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("a specification with another format version was accepted"),
+    }
+}
+
+#[test]
+fn malformed_input_is_rejected() {
+    let inputs = [
+        "not json".to_string(),
+        format!("{{\"version\":{},\"spec\":{{\"kind\":{{\"Unknown\":[]}}}}}}", FORMAT_VERSION),
+        "{\"version\":\"1\"}".to_string(),
+        "pc1:1;3:abc{0;".to_string(),
+        "pc1:2;7:version1:x{0;u1;1;s5;}".to_string(),
+    ];
+    for json in inputs.iter() {
+        match json::Assertion::from_json_string(json) {
+            Err(DecodeError::Malformed(_)) => {}
+            Err(err) => panic!("unexpected error for `{}`: {}", json, err),
+            Ok(_) => panic!("malformed input `{}` was accepted", json),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Tests of the structure that the parser gives to assertions.
+
+use prusti_specs::specifications::common::AssertionKind;
+use prusti_specs::specifications::preparser::{AssertionWithoutId, Parser};
+use quote::ToTokens;
+
+/// Print the structure of an assertion, e.g. `Or(And(a, b), c)`.
+fn show(assertion: &AssertionWithoutId) -> String {
+    let show_all = |assertions: &[AssertionWithoutId]| {
+        assertions.iter().map(show).collect::<Vec<_>>().join(", ")
+    };
+    match &*assertion.kind {
+        AssertionKind::Expr(expression) => expression.expr.to_token_stream().to_string(),
+        AssertionKind::And(conjuncts) => format!("And({})", show_all(conjuncts)),
+        AssertionKind::Or(disjuncts) => format!("Or({})", show_all(disjuncts)),
+        AssertionKind::Not(operand) => format!("Not({})", show(operand)),
+        AssertionKind::Implies(lhs, rhs) => format!("Implies({}, {})", show(lhs), show(rhs)),
+        AssertionKind::Iff(lhs, rhs) => format!("Iff({}, {})", show(lhs), show(rhs)),
+        AssertionKind::TypeCond(_, body) => format!("TypeCond({})", show(body)),
+        AssertionKind::ForAll(_, _, body) => format!("ForAll({})", show(body)),
+        AssertionKind::Exists(_, _, body) => format!("Exists({})", show(body)),
+    }
+}
+
+fn parse(assertion: &str) -> syn::Result<AssertionWithoutId> {
+    Parser::from_token_stream(assertion.parse().unwrap()).extract_assertion()
+}
+
+fn assert_parsed(assertion: &str, expected: &str) {
+    match parse(assertion) {
+        Ok(parsed) => assert_eq!(show(&parsed), expected, "`{}` is parsed wrongly", assertion),
+        Err(err) => panic!("`{}` is rejected: {}", assertion, err),
+    }
+}
+
+fn assert_rejected(assertion: &str, expected: &str) {
+    match parse(assertion) {
+        Ok(parsed) => panic!("`{}` is parsed as `{}`", assertion, show(&parsed)),
+        Err(err) => assert!(
+            err.to_string().contains(expected),
+            "`{}` is rejected with `{}`",
+            assertion,
+            err,
+        ),
+    }
+}
+
+#[test]
+fn negation() {
+    assert_parsed("!(a ==> b)", "Not(Implies(a, b))");
+    assert_parsed("!!forall(|i: u32| i > 0)", "Not(Not(ForAll(i > 0)))");
+    assert_parsed("!(a ==> b) && c", "And(Not(Implies(a, b)), c)");
+    // a negation that is followed by more of an expression is Rust's
+    assert_parsed("!(a) == b", "! (a) == b");
+}
+
+#[test]
+fn disjunction() {
+    assert_parsed("a || b || c", "Or(a, b, c)");
+    assert_parsed("a || b ==> c", "Implies(Or(a, b), c)");
+    assert_parsed("a ==> b || forall(|i: u32| i > 0)", "Implies(a, Or(b, ForAll(i > 0)))");
+}
+
+#[test]
+fn parenthesized_mix_of_conjunction_and_disjunction() {
+    assert_parsed("(a && b) || c", "Or(And(a, b), c)");
+    assert_parsed("a && (b || c)", "And(a, Or(b, c))");
+    assert_parsed("a && b ==> c || d", "Implies(And(a, b), Or(c, d))");
+}
+
+#[test]
+fn unparenthesized_mix_of_conjunction_and_disjunction_is_ambiguous() {
+    let message = "found `||` and `&&` in the same subexpression";
+    assert_rejected("a && b || c", message);
+    assert_rejected("a || b && c", message);
+    assert_rejected("x ==> (a || b && c)", message);
+    assert_rejected("forall(|i: u32| a && b || c)", message);
+}
+
+#[test]
+fn quantifiers() {
+    assert_parsed("forall(|i: u32| i > 0)", "ForAll(i > 0)");
+    assert_parsed("exists(|i: u32| i > 0)", "Exists(i > 0)");
+    assert_parsed(
+        "exists(|i: u32| f(i), triggers = [(f(i),)]) && forall(|j: u32| exists(|k: u32| j < k))",
+        "And(Exists(f (i)), ForAll(Exists(j < k)))",
+    );
+}
+
+#[test]
+fn methods_and_variables_named_like_quantifiers() {
+    assert_parsed("p.forall() && p.exists()", "And(p . forall (), p . exists ())");
+    assert_parsed("forall || exists", "Or(forall, exists)");
+    assert_parsed("p.exists() ==> exists(|i: u32| i > 0)", "Implies(p . exists (), Exists(i > 0))");
+}
+
+#[test]
+fn malformed_quantifiers() {
+    for quantifier in &["forall", "exists"] {
+        assert_rejected(&format!("{}(|| true)", quantifier), "at least one argument");
+        assert_rejected(&format!("{}(i: u32, i > 0)", quantifier), "expected `|`");
+        assert_rejected(&format!("{} |i: u32| i > 0", quantifier), "expected `(`");
+    }
+}
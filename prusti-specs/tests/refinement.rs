@@ -0,0 +1,139 @@
+//! Tests of how the specification of a trait implementation method is
+//! combined with the specification of the trait method.
+
+use prusti_specs::specifications::common::{
+    Assertion, AssertionKind, Expression, Pledge, ProcedureSpecification, RefinementMode,
+    SpecificationId, SpecificationSet, SubtypingObligation, SubtypingObligationKind,
+};
+
+type Spec = SpecificationSet<(), &'static str, ()>;
+
+fn expr(expr: &'static str) -> Assertion<(), &'static str, ()> {
+    Assertion {
+        kind: Box::new(AssertionKind::Expr(Expression {
+            spec_id: SpecificationId::default(),
+            id: (),
+            expr,
+        })),
+    }
+}
+
+fn pledge(rhs: &'static str) -> Pledge<(), &'static str, ()> {
+    Pledge { reference: None, lhs: None, rhs: expr(rhs) }
+}
+
+fn procedure(pres: &[&'static str], posts: &[&'static str], pledges: &[&'static str]) -> Spec {
+    SpecificationSet::Procedure(ProcedureSpecification::new(
+        pres.iter().map(|pre| expr(pre)).collect(),
+        posts.iter().map(|post| expr(post)).collect(),
+        pledges.iter().map(|rhs| pledge(rhs)).collect(),
+    ))
+}
+
+/// Print an assertion, e.g. `Or(And(a, b), c)`.
+fn show(assertion: &Assertion<(), &'static str, ()>) -> String {
+    let show_all = |assertions: &[Assertion<(), &'static str, ()>]| {
+        assertions.iter().map(show).collect::<Vec<_>>().join(", ")
+    };
+    match &*assertion.kind {
+        AssertionKind::Expr(expression) => expression.expr.to_string(),
+        AssertionKind::And(conjuncts) => format!("And({})", show_all(conjuncts)),
+        AssertionKind::Or(disjuncts) => format!("Or({})", show_all(disjuncts)),
+        kind => panic!("unexpected assertion: {:?}", kind),
+    }
+}
+
+/// Print the preconditions, postconditions and pledges of a specification.
+fn show_spec(spec: &Spec) -> (Vec<String>, Vec<String>, Vec<String>) {
+    match spec {
+        SpecificationSet::Procedure(spec) => (
+            spec.pres.iter().map(show).collect(),
+            spec.posts.iter().map(show).collect(),
+            spec.pledges.iter().map(|pledge| show(&pledge.rhs)).collect(),
+        ),
+        _ => panic!("unexpected specification: {:?}", spec),
+    }
+}
+
+fn show_obligation(obligation: &SubtypingObligation<(), &'static str, ()>) -> String {
+    let show_all = |assertions: &[Assertion<(), &'static str, ()>]| {
+        assertions.iter().map(show).collect::<Vec<_>>().join(", ")
+    };
+    let kind = match obligation.kind {
+        SubtypingObligationKind::Precondition => "pre",
+        SubtypingObligationKind::Postcondition => "post",
+    };
+    format!("{}: [{}] ==> [{}]", kind, show_all(&obligation.lhs), show_all(&obligation.rhs))
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+#[test]
+fn extend_disjoins_preconditions_and_conjoins_the_rest() {
+    let base = procedure(&["a", "b"], &["p"], &["x"]);
+    let refinement = procedure(&["c"], &["q", "r"], &["y"]);
+    let (pres, posts, pledges) = show_spec(&base.refine(&refinement, RefinementMode::Extend));
+    assert_eq!(pres, strings(&["Or(And(a, b), And(c))"]));
+    assert_eq!(posts, strings(&["p", "q", "r"]));
+    assert_eq!(pledges, strings(&["x", "y"]));
+}
+
+#[test]
+fn extend_keeps_an_empty_trait_precondition() {
+    // the empty precondition of the trait is `true`, so is the disjunction
+    let base = procedure(&[], &["p"], &[]);
+    let refinement = procedure(&["c"], &["q"], &[]);
+    let (pres, posts, pledges) = show_spec(&base.refine(&refinement, RefinementMode::Extend));
+    assert!(pres.is_empty());
+    assert_eq!(posts, strings(&["p", "q"]));
+    assert!(pledges.is_empty());
+}
+
+#[test]
+fn extend_with_an_empty_implementation_precondition() {
+    let base = procedure(&["a"], &[], &["x"]);
+    let refinement = procedure(&[], &[], &[]);
+    let (pres, posts, pledges) = show_spec(&base.refine(&refinement, RefinementMode::Extend));
+    assert_eq!(pres, strings(&["a"]));
+    assert!(posts.is_empty());
+    assert_eq!(pledges, strings(&["x"]));
+
+    let (pres, _, _) = show_spec(&procedure(&[], &[], &[]).refine(&refinement, RefinementMode::Extend));
+    assert!(pres.is_empty());
+}
+
+#[test]
+fn overwrite_replaces_the_refined_parts() {
+    let base = procedure(&["a"], &["p"], &["x"]);
+    let refinement = procedure(&["c"], &[], &["y"]);
+    let (pres, posts, pledges) = show_spec(&base.refine(&refinement, RefinementMode::Overwrite));
+    assert_eq!(pres, strings(&["c"]));
+    assert_eq!(posts, strings(&["p"]));
+    assert_eq!(pledges, strings(&["y"]));
+}
+
+#[test]
+fn extend_has_no_subtyping_obligations() {
+    let base = procedure(&["a"], &["p"], &[]);
+    let refinement = procedure(&["c"], &["q"], &[]);
+    assert!(base.subtyping_obligations(&refinement, RefinementMode::Extend).is_empty());
+}
+
+#[test]
+fn overwrite_has_obligations_for_the_refined_parts() {
+    let base = procedure(&["a"], &["p"], &[]);
+    let obligations = |refinement: &Spec| -> Vec<String> {
+        base.subtyping_obligations(refinement, RefinementMode::Overwrite)
+            .iter()
+            .map(show_obligation)
+            .collect()
+    };
+    assert_eq!(
+        obligations(&procedure(&["c"], &["q"], &[])),
+        strings(&["pre: [a] ==> [c]", "post: [q] ==> [p]"]),
+    );
+    assert_eq!(obligations(&procedure(&[], &["q"], &[])), strings(&["post: [q] ==> [p]"]));
+    assert!(obligations(&procedure(&[], &[], &["y"])).is_empty());
+}
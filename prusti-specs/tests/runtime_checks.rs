@@ -0,0 +1,77 @@
+//! Tests of the translation of quantifiers into runtime checks.
+
+use prusti_specs::runtime_checks::{body_invariant, UnboundedQuantifierPolicy};
+
+/// Translate the assertion and return the generated code without whitespace.
+fn translate(assertion: &str, policy: UnboundedQuantifierPolicy) -> String {
+    body_invariant(assertion.parse().unwrap(), policy)
+        .to_string()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+fn assert_translated(assertion: &str, expected: &str) {
+    let code = translate(assertion, UnboundedQuantifierPolicy::Warn);
+    assert!(code.contains(expected), "`{}` is translated to `{}`", assertion, code);
+    assert!(!code.contains("unbounded_quantifier"), "`{}` is reported as unbounded", assertion);
+}
+
+fn assert_unbounded(assertion: &str) {
+    let code = translate(assertion, UnboundedQuantifierPolicy::Warn);
+    assert!(code.contains("unbounded_quantifier"), "`{}` is not reported as unbounded", assertion);
+    assert!(!code.contains(".all(") && !code.contains(".any("), "`{}` is checked: `{}`", assertion, code);
+    let code = translate(assertion, UnboundedQuantifierPolicy::Skip);
+    assert!(!code.contains("unbounded_quantifier"), "`{}` is reported although skipped", assertion);
+}
+
+#[test]
+fn forall_with_exclusive_bounds() {
+    assert_translated(
+        "forall(|i: i32| 0 <= i && i < n ==> a[i] > 0)",
+        "((0)..(n)).all(|i:i32|(a[i]>0))",
+    );
+}
+
+#[test]
+fn forall_with_inclusive_bounds() {
+    assert_translated(
+        "forall(|i: i32| i >= 1 && n >= i ==> a[i] > 0)",
+        "((1)..=(n)).all(|i:i32|(a[i]>0))",
+    );
+}
+
+#[test]
+fn exists_with_exclusive_bounds() {
+    assert_translated(
+        "exists(|i: i64| -5 < i && i < n && a[i] == 0)",
+        "((-5)..(n)).skip(1).any(|i:i64|((a[i]==0)))",
+    );
+}
+
+#[test]
+fn exists_with_inclusive_bounds() {
+    assert_translated(
+        "exists(|i: i64| -5 <= i && i <= n && a[i] == 0)",
+        "((-5)..=(n)).any(|i:i64|((a[i]==0)))",
+    );
+}
+
+#[test]
+fn unsigned_variables_are_bounded_by_zero() {
+    assert_translated(
+        "forall(|i: usize| i < a.len() ==> a[i] > 0)",
+        "((0)..(a.len())).all(|i:usize|(a[i]>0))",
+    );
+    assert_translated(
+        "exists(|j: u8, i: u8| j < 10 && i <= j && a[i] == a[j])",
+        "((0)..=(j))",
+    );
+}
+
+#[test]
+fn unbounded_quantifiers_are_not_checked() {
+    assert_unbounded("forall(|i: i32| i < n ==> a[i] > 0)");
+    assert_unbounded("exists(|i: usize| a[i] == 0)");
+    assert_unbounded("forall(|i: usize, j: usize| i < j ==> a[i] <= a[j])");
+}
@@ -0,0 +1,22 @@
+//! Tests of the selection of the encoding by `PRUSTI_SPEC_ENCODING`. They are
+//! kept in their own test binary, because they set the environment variable
+//! for the whole process.
+
+use prusti_specs::{rewrite_prusti_attributes, SpecAttributeKind};
+
+#[test]
+fn invalid_encoding_is_reported() {
+    std::env::set_var("PRUSTI_SPEC_ENCODING", "yaml");
+    let expanded = rewrite_prusti_attributes(
+        SpecAttributeKind::Requires,
+        "x > 0".parse().unwrap(),
+        "fn f(x: u32) {}".parse().unwrap(),
+    )
+    .to_string();
+    assert!(expanded.contains("compile_error"), "the encoding is not reported: `{}`", expanded);
+    assert!(
+        expanded.contains("invalid value `yaml` of PRUSTI_SPEC_ENCODING, expected `json` or `compact`"),
+        "the valid encodings are not listed: `{}`",
+        expanded,
+    );
+}
@@ -0,0 +1,110 @@
+//! Tests of how termination measures, given with `#[decreases]` and
+//! `body_variant!`, are parsed and serialized.
+
+use proc_macro2::{TokenStream, TokenTree};
+use prusti_specs::specifications::json;
+use prusti_specs::{rewrite_prusti_attributes, SpecAttributeKind};
+
+/// Find the value of the `prusti::variant` attribute in the expansion.
+fn find_variant(tokens: TokenStream) -> Option<String> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    for (index, token) in tokens.iter().enumerate() {
+        match (token, tokens.get(index + 1), tokens.get(index + 2)) {
+            (TokenTree::Ident(ident), Some(TokenTree::Punct(eq)), Some(TokenTree::Literal(lit)))
+                if ident == "variant" && eq.as_char() == '=' =>
+            {
+                let lit: syn::LitStr = syn::parse_str(&lit.to_string()).unwrap();
+                return Some(lit.value());
+            }
+            (TokenTree::Group(group), ..) => {
+                if let Some(json) = find_variant(group.stream()) {
+                    return Some(json);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Decode the serialized termination measure in the expansion and return
+/// the source of its components, which are all on the single line of
+/// `measure`.
+fn components(expanded: TokenStream, measure: &str) -> Result<Vec<String>, String> {
+    let json = match find_variant(expanded.clone()) {
+        Some(json) => json,
+        None => return Err(expanded.to_string()),
+    };
+    let variant = json::Variant::from_json_string(&json).unwrap();
+    let spec_ids: Vec<_> = variant.exprs.iter().map(|expr| expr.spec_id).collect();
+    assert!(
+        spec_ids.windows(2).all(|ids| ids[0] == ids[1]),
+        "the components belong to different specifications",
+    );
+    Ok(variant.exprs.iter()
+        .map(|expr| measure[expr.location.start.column..expr.location.end.column].to_string())
+        .collect())
+}
+
+fn decreases(measure: &str) -> Result<Vec<String>, String> {
+    let expanded = rewrite_prusti_attributes(
+        SpecAttributeKind::Decreases,
+        measure.parse().unwrap(),
+        "fn f(n: u32, m: u32) {}".parse().unwrap(),
+    );
+    components(expanded, measure)
+}
+
+fn body_variant(measure: &str) -> Result<Vec<String>, String> {
+    components(prusti_specs::body_variant(measure.parse().unwrap()), measure)
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+#[test]
+fn single_expression() {
+    assert_eq!(decreases("n"), Ok(strings(&["n"])));
+    assert_eq!(body_variant("n - i"), Ok(strings(&["n - i"])));
+}
+
+#[test]
+fn lexicographic_list() {
+    assert_eq!(decreases("n, m + 1"), Ok(strings(&["n", "m + 1"])));
+    assert_eq!(body_variant("n - i, m,"), Ok(strings(&["n - i", "m"])));
+}
+
+#[test]
+fn lexicographic_tuple_is_the_list_of_its_elements() {
+    assert_eq!(decreases("(n, m + 1)"), Ok(strings(&["n", "m + 1"])));
+    assert_eq!(body_variant("(n - i, m)"), Ok(strings(&["n - i", "m"])));
+    // only a single tuple is flattened
+    assert_eq!(decreases("(n, m), n"), Ok(strings(&["(n, m)", "n"])));
+}
+
+#[test]
+fn serialized_ids_are_numbered_in_order() {
+    let expanded = rewrite_prusti_attributes(
+        SpecAttributeKind::Decreases,
+        "n, m".parse().unwrap(),
+        "fn f(n: u32, m: u32) {}".parse().unwrap(),
+    );
+    let variant = json::Variant::from_json_string(&find_variant(expanded).unwrap()).unwrap();
+    let ids: Vec<u64> = variant.exprs.into_iter().map(|expr| expr.expr_id.into()).collect();
+    assert_eq!(ids, vec![101, 102]);
+}
+
+#[test]
+fn empty_measure_is_rejected() {
+    for result in &[decreases(""), body_variant("")] {
+        match result {
+            Err(expanded) => assert!(
+                expanded.contains("a termination measure must have at least one expression"),
+                "unexpected expansion: {}",
+                expanded,
+            ),
+            Ok(components) => panic!("an empty measure is parsed as {:?}", components),
+        }
+    }
+}